@@ -0,0 +1,211 @@
+//! Headless map generation: runs a generator and prints the resulting map as ASCII.
+//!
+//! ```text
+//! mapgen [--generator rnc|ugly] [--seed N] [--config rnc.yaml]
+//!        [--txt map.txt] [--png map.png] [--json summary.json] [--events events.jsonl]
+//!        [--save map.ron] [--quiet]
+//! ```
+
+use rand::Rng;
+use serde::Serialize;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+use std::time::Instant;
+use tutrl::map::events::{GenObserver, JsonLinesSink};
+use tutrl::map::gen::{self, rnc, Generated};
+use tutrl::map::stats::MapStats;
+use tutrl::map::{Map, Tile};
+use tutrl::utils::args::Args;
+
+/// Side (in pixels) of a tile in PNG exports
+const PNG_TILE_SIZE: usize = 4;
+
+const USAGE: &str = "\
+Usage: mapgen [OPTIONS]
+
+Options:
+    -g, --generator NAME  generator to run: rnc (default) or ugly
+    -s, --seed N          seed of the generator (random if missing)
+    -c, --config PATH     YAML generator config (same fields as `rnc::Config`)
+        --txt PATH        also write the map as text to PATH
+        --png PATH        also write the map as a PNG image to PATH
+        --json PATH       write a JSON summary of the generation to PATH
+        --events PATH     write the generation events to PATH (as JSON lines)
+        --save PATH       save the map to PATH (as RON if it ends in .ron, in binary otherwise)
+    -q, --quiet           don't print the map
+    -h, --help            print this message";
+
+/// Command line options
+struct Options {
+    generator: String,
+    seed: Option<u64>,
+    config: Option<String>,
+    txt: Option<String>,
+    png: Option<String>,
+    json: Option<String>,
+    events: Option<String>,
+    save: Option<String>,
+    quiet: bool,
+}
+
+impl Options {
+    /// Parse options from command line arguments (program name excluded)
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self, Box<dyn Error>> {
+        let mut opts = Options {
+            generator: "rnc".to_string(),
+            seed: None,
+            config: None,
+            txt: None,
+            png: None,
+            json: None,
+            events: None,
+            save: None,
+            quiet: false,
+        };
+
+        let mut args = Args::new(args, USAGE);
+        while let Some(option) = args.next_option() {
+            match option.as_str() {
+                "-g" | "--generator" => opts.generator = args.value()?,
+                "-s" | "--seed" => opts.seed = Some(args.parse_value()?),
+                "-c" | "--config" => opts.config = Some(args.value()?),
+                "--txt" => opts.txt = Some(args.value()?),
+                "--png" => opts.png = Some(args.value()?),
+                "--json" => opts.json = Some(args.value()?),
+                "--events" => opts.events = Some(args.value()?),
+                "--save" => opts.save = Some(args.value()?),
+                "-q" | "--quiet" => opts.quiet = true,
+                _ => return Err(args.unknown().into()),
+            }
+        }
+
+        Ok(opts)
+    }
+}
+
+/// What is written to the JSON summary
+#[derive(Serialize)]
+struct Summary<'a> {
+    generator: &'a str,
+    seed: u64,
+    /// False if the generator stopped prematurely (returning a partial map)
+    complete: bool,
+    generation_ms: f64,
+    #[serde(flatten)]
+    stats: MapStats,
+}
+
+/// Loads the generator config given in `opts` (the game one if missing)
+fn load_config(opts: &Options) -> Result<rnc::Config, Box<dyn Error>> {
+    Ok(match &opts.config {
+        Some(path) => rnc::Config::load(path)?,
+        None => rnc::Config::game(),
+    })
+}
+
+/// Colour of `tile` in PNG exports
+fn tile_rgb(tile: Tile) -> [u8; 3] {
+    match tile {
+        Tile::Wall => [40, 40, 40],
+        Tile::Floor => [200, 200, 200],
+        Tile::ClosedDoor | Tile::OpenDoor => [139, 69, 19],
+        Tile::LockedDoor => [255, 215, 0],
+        Tile::Bookshelf => [160, 82, 45],
+        Tile::ShallowWater => [65, 105, 225],
+        Tile::TallGrass => [34, 139, 34],
+    }
+}
+
+/// Encodes `map` as a PNG image (each tile being a square of `PNG_TILE_SIZE` pixels)
+fn to_png(map: &Map) -> Result<Vec<u8>, png::EncodingError> {
+    let (width, height) = (map.width * PNG_TILE_SIZE, map.height * PNG_TILE_SIZE);
+    let mut pixels = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            let tile = map.at(x / PNG_TILE_SIZE, y / PNG_TILE_SIZE);
+            pixels.extend_from_slice(&tile_rgb(tile));
+        }
+    }
+
+    let mut png = vec![];
+    let mut encoder = png::Encoder::new(&mut png, width as u32, height as u32);
+    encoder.set_color(png::ColorType::RGB);
+    encoder.set_depth(png::BitDepth::Eight);
+    // The image is complete once the writer is dropped.
+    encoder.write_header()?.write_image_data(&pixels)?;
+    Ok(png)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let opts = Options::parse(std::env::args().skip(1))?;
+
+    // The seed on the command line wins over the config one.
+    let conf = load_config(&opts)?;
+    let seed = opts
+        .seed
+        .or_else(|| conf.seed())
+        .unwrap_or_else(|| rand::thread_rng().gen());
+
+    // Events are only written if asked to.
+    let mut sink = match &opts.events {
+        Some(path) => Some(JsonLinesSink::new(BufWriter::new(File::create(path)?))),
+        None => None,
+    };
+    let mut nobody = ();
+    let observer: &mut dyn GenObserver = match &mut sink {
+        Some(sink) => sink,
+        None => &mut nobody,
+    };
+
+    let start = Instant::now();
+    let Generated { map, complete } = gen::run(&opts.generator, conf, seed, observer)?;
+    let elapsed = start.elapsed();
+    if let Some(sink) = sink {
+        sink.finish()?;
+    }
+
+    if !opts.quiet {
+        print!("{}", map);
+    }
+    if let Some(path) = &opts.txt {
+        std::fs::write(path, map.to_string())?;
+    }
+    if let Some(path) = &opts.png {
+        std::fs::write(path, to_png(&map)?)?;
+    }
+    if let Some(path) = &opts.save {
+        map.save(path)?;
+    }
+    if let Some(path) = &opts.json {
+        let summary = Summary {
+            generator: &opts.generator,
+            seed,
+            complete,
+            generation_ms: elapsed.as_secs_f64() * 1000.,
+            stats: MapStats::of(&map),
+        };
+        std::fs::write(path, serde_json::to_string_pretty(&summary)?)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn png_has_one_square_per_tile() {
+        let map = Map::empty(2, 3);
+        let png = to_png(&map).unwrap();
+
+        let (info, mut reader) = png::Decoder::new(&png[..]).read_info().unwrap();
+        assert_eq!(info.width, 2 * PNG_TILE_SIZE as u32);
+        assert_eq!(info.height, 3 * PNG_TILE_SIZE as u32);
+
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        assert_eq!(&pixels[..3], &tile_rgb(map.at(0, 0)));
+    }
+}
@@ -0,0 +1,177 @@
+//! Batch map generation: runs a generator many times per config and reports how the maps turned
+//! out (to catch configs producing tiny or broken maps).
+//!
+//! ```text
+//! mapstats [--generator rnc|ugly] [--runs N] [--seed N] [--config rnc.yaml]... [--json out.json]
+//! ```
+
+use rand::Rng;
+use serde::Serialize;
+use std::error::Error;
+use tutrl::map::gen::{self, rnc};
+use tutrl::map::stats::{Distribution, MapStats};
+use tutrl::utils::args::Args;
+
+const USAGE: &str = "\
+Usage: mapstats [OPTIONS]
+
+Options:
+    -g, --generator NAME  generator to run: rnc (default) or ugly
+    -n, --runs N          maps to generate per config (default: 100)
+    -s, --seed N          seed of the first run, the others follow (random if missing)
+    -c, --config PATH     YAML generator config (can be repeated, default: the game one)
+        --json PATH       also write the reports as JSON to PATH
+    -h, --help            print this message";
+
+/// Command line options
+struct Options {
+    generator: String,
+    runs: u64,
+    seed: Option<u64>,
+    configs: Vec<String>,
+    json: Option<String>,
+}
+
+impl Options {
+    /// Parse options from command line arguments (program name excluded)
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self, Box<dyn Error>> {
+        let mut opts = Options {
+            generator: "rnc".to_string(),
+            runs: 100,
+            seed: None,
+            configs: vec![],
+            json: None,
+        };
+
+        let mut args = Args::new(args, USAGE);
+        while let Some(option) = args.next_option() {
+            match option.as_str() {
+                "-g" | "--generator" => opts.generator = args.value()?,
+                "-n" | "--runs" => opts.runs = args.parse_value()?,
+                "-s" | "--seed" => opts.seed = Some(args.parse_value()?),
+                "-c" | "--config" => opts.configs.push(args.value()?),
+                "--json" => opts.json = Some(args.value()?),
+                _ => return Err(args.unknown().into()),
+            }
+        }
+
+        Ok(opts)
+    }
+}
+
+/// How the maps generated with a config turned out
+#[derive(Serialize)]
+struct Report {
+    config: String,
+    generator: String,
+    runs: u64,
+    first_seed: u64,
+    /// Share of the runs where the generator stopped prematurely (returning a partial map)
+    incomplete_share: f32,
+    floor_ratio: Option<Distribution>,
+    rooms: Option<Distribution>,
+    areas: Option<Distribution>,
+    largest_area: Option<Distribution>,
+    largest_area_ratio: Option<Distribution>,
+    dead_ends: Option<Distribution>,
+    /// Over the maps with at least two connected rooms
+    mean_room_distance: Option<Distribution>,
+}
+
+impl Report {
+    /// Runs `opts.generator` `opts.runs` times with `conf` (seeds start from `first_seed`)
+    fn generate(
+        opts: &Options,
+        name: &str,
+        conf: &rnc::Config,
+        first_seed: u64,
+    ) -> Result<Self, String> {
+        let mut stats = vec![];
+        let mut incomplete = 0;
+        for seed in (0..opts.runs).map(|i| first_seed.wrapping_add(i)) {
+            let generated = gen::run(&opts.generator, conf.clone(), seed, &mut ())?;
+            if !generated.complete {
+                incomplete += 1;
+            }
+            stats.push(MapStats::of(&generated.map));
+        }
+
+        let dist = |stat: fn(&MapStats) -> Option<f32>| {
+            let values: Vec<f32> = stats.iter().filter_map(stat).collect();
+            Distribution::of(&values)
+        };
+        Ok(Report {
+            config: name.to_string(),
+            generator: opts.generator.clone(),
+            runs: opts.runs,
+            first_seed,
+            incomplete_share: incomplete as f32 / opts.runs.max(1) as f32,
+            floor_ratio: dist(|s| Some(s.floor_ratio)),
+            rooms: dist(|s| Some(s.rooms as f32)),
+            areas: dist(|s| Some(s.areas as f32)),
+            largest_area: dist(|s| Some(s.largest_area as f32)),
+            largest_area_ratio: dist(|s| Some(s.largest_area_ratio)),
+            dead_ends: dist(|s| Some(s.dead_ends as f32)),
+            mean_room_distance: dist(|s| s.mean_room_distance),
+        })
+    }
+
+    /// Prints the report as a table
+    fn print(&self) {
+        println!(
+            "{} ({} generator, {} runs, seeds from {})",
+            self.config, self.generator, self.runs, self.first_seed
+        );
+        println!("  incomplete runs: {:.1}%", self.incomplete_share * 100.);
+        println!(
+            "  {:<22}{:>10}{:>10}{:>10}{:>10}",
+            "", "min", "mean", "median", "max"
+        );
+        let rows = [
+            ("floor ratio", &self.floor_ratio),
+            ("rooms", &self.rooms),
+            ("areas", &self.areas),
+            ("largest area", &self.largest_area),
+            ("largest area ratio", &self.largest_area_ratio),
+            ("dead ends", &self.dead_ends),
+            ("mean room distance", &self.mean_room_distance),
+        ];
+        for (name, dist) in rows.iter() {
+            match dist {
+                Some(d) => println!(
+                    "  {:<22}{:>10.2}{:>10.2}{:>10.2}{:>10.2}",
+                    name, d.min, d.mean, d.median, d.max
+                ),
+                None => println!("  {:<22}{:>10}", name, "-"),
+            }
+        }
+        println!();
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let opts = Options::parse(std::env::args().skip(1))?;
+    let first_seed = opts.seed.unwrap_or_else(|| rand::thread_rng().gen());
+
+    let configs = if opts.configs.is_empty() {
+        vec![("game config".to_string(), rnc::Config::game())]
+    } else {
+        opts.configs
+            .iter()
+            .map(|path| Ok((path.clone(), rnc::Config::load(path)?)))
+            .collect::<Result<_, Box<dyn Error>>>()?
+    };
+
+    let mut reports = vec![];
+    for (name, conf) in &configs {
+        let report = Report::generate(&opts, name, conf, first_seed)?;
+        report.print();
+        reports.push(report);
+    }
+
+    if let Some(path) = &opts.json {
+        std::fs::write(path, serde_json::to_string_pretty(&reports)?)?;
+    }
+
+    Ok(())
+}
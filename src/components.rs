@@ -1,4 +1,5 @@
-use rltk::{RGB};
+use rltk::RGB;
+use serde::Deserialize;
 use specs::prelude::*;
 use specs_derive::Component;
 use rltk::Point;
@@ -25,17 +26,404 @@ pub struct Renderable {
 #[derive(Component)]
 pub struct Player;
 
+/// Human readable name of an entity (used e.g. by tooltips).
+#[derive(Component)]
+pub struct Name {
+    pub name: String,
+}
+
+impl From<&str> for Name {
+    fn from(name: &str) -> Self {
+        Name {
+            name: name.to_string(),
+        }
+    }
+}
+
+/// How far entities can see in the dark (i.e. on unlit tiles)
+pub const NIGHT_VISION_RANGE: i32 = 1;
+
 #[derive(Component, Default)]
 pub struct Viewshed {
     pub visible_tiles: Vec<rltk::Point>,
     pub range: i32,
+    /// Unlit tiles are only visible within this range
+    pub night_vision: i32,
 }
 
 impl Viewshed {
     pub fn new(range: i32) -> Self {
         Viewshed {
             range,
+            night_vision: NIGHT_VISION_RANGE,
             ..Default::default()
         }
     }
 }
+
+/// Lights up the tiles around the entity
+#[derive(Component)]
+pub struct LightSource {
+    pub radius: i32,
+    pub colour: RGB,
+}
+
+/// Marks entities that can be picked up
+#[derive(Component)]
+pub struct Item;
+
+/// Item is stored in the backpack of `owner`
+#[derive(Component)]
+pub struct InBackpack {
+    pub owner: Entity,
+}
+
+/// Entity wants to pick up `item` from the ground
+#[derive(Component)]
+pub struct WantsToPickup {
+    pub item: Entity,
+}
+
+/// Entity wants to drop `item` from its backpack
+#[derive(Component)]
+pub struct WantsToDrop {
+    pub item: Entity,
+}
+
+/// Basic fighting statistics
+#[derive(Component)]
+pub struct CombatStats {
+    pub max_hp: i32,
+    pub hp: i32,
+    pub defense: i32,
+    pub power: i32,
+}
+
+/// Entity wants to use `item` (on `target` if the item is `Ranged`)
+#[derive(Component)]
+pub struct WantsToUseItem {
+    pub item: Entity,
+    pub target: Option<Point>,
+}
+
+/// Item disappears once used
+#[derive(Component)]
+pub struct Consumable;
+
+/// Item needs to be aimed at a tile at most `range` tiles away from the user
+#[derive(Component)]
+pub struct Ranged {
+    pub range: i32,
+}
+
+/// Item affects everything within `radius` tiles of its target
+#[derive(Component)]
+pub struct AreaOfEffect {
+    pub radius: i32,
+}
+
+/// Item heals its targets
+#[derive(Component)]
+pub struct ProvidesHealing {
+    pub heal_amount: i32,
+}
+
+/// Item damages its targets
+#[derive(Component)]
+pub struct InflictsDamage {
+    pub damage: i32,
+}
+
+/// Item confuses its targets for some turns
+#[derive(Component)]
+pub struct Confusion {
+    pub turns: i32,
+}
+
+/// Item teleports its targets to a random spot of the map
+#[derive(Component)]
+pub struct Teleports;
+
+/// Item blinds its targets for some turns
+#[derive(Component)]
+pub struct Blinds {
+    pub turns: i32,
+}
+
+/// Item poisons its targets for some turns
+#[derive(Component)]
+pub struct InflictsPoison {
+    pub turns: i32,
+    pub damage: i32,
+}
+
+/// Item makes its targets regenerate for some turns
+#[derive(Component)]
+pub struct ProvidesRegeneration {
+    pub turns: i32,
+    pub amount: i32,
+}
+
+/// Item can be eaten
+#[derive(Component)]
+pub struct ProvidesFood;
+
+/// A status that wears off (or changes) after some turns
+pub trait TimedStatus: Component + Send + Sync {
+    /// Turns left before the status runs out
+    fn turns_mut(&mut self) -> &mut i32;
+
+    /// Called when the status runs out: returns `true` if it should be kept (e.g. because it
+    /// moved on to its next stage)
+    fn on_expire(&mut self) -> bool {
+        false
+    }
+
+    /// Message telling the player what happened when the status ran out
+    fn expire_message(&self) -> String;
+}
+
+/// Entity is confused (it stumbles in random directions) for some more turns
+#[derive(Component)]
+pub struct Confused {
+    pub turns: i32,
+}
+
+impl TimedStatus for Confused {
+    fn turns_mut(&mut self) -> &mut i32 {
+        &mut self.turns
+    }
+
+    fn expire_message(&self) -> String {
+        "You are no longer confused.".to_string()
+    }
+}
+
+/// Entity is blind (its viewshed shrinks) for some more turns
+#[derive(Component)]
+pub struct Blind {
+    pub turns: i32,
+}
+
+impl TimedStatus for Blind {
+    fn turns_mut(&mut self) -> &mut i32 {
+        &mut self.turns
+    }
+
+    fn expire_message(&self) -> String {
+        "You can see again.".to_string()
+    }
+}
+
+/// Entity loses `damage` hp per turn for some more turns
+#[derive(Component)]
+pub struct Poisoned {
+    pub turns: i32,
+    pub damage: i32,
+}
+
+impl TimedStatus for Poisoned {
+    fn turns_mut(&mut self) -> &mut i32 {
+        &mut self.turns
+    }
+
+    fn expire_message(&self) -> String {
+        "The poison wears off.".to_string()
+    }
+}
+
+/// Entity heals `amount` hp per turn for some more turns
+#[derive(Component)]
+pub struct Regenerating {
+    pub turns: i32,
+    pub amount: i32,
+}
+
+impl TimedStatus for Regenerating {
+    fn turns_mut(&mut self) -> &mut i32 {
+        &mut self.turns
+    }
+
+    fn expire_message(&self) -> String {
+        "You stop regenerating.".to_string()
+    }
+}
+
+/// How hungry an entity is
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum HungerState {
+    WellFed,
+    Normal,
+    Hungry,
+    Starving,
+}
+
+impl HungerState {
+    /// Turns spent in this state before moving on to the next one
+    pub fn duration(self) -> i32 {
+        match self {
+            HungerState::WellFed => 200,
+            HungerState::Normal => 300,
+            HungerState::Hungry => 200,
+            HungerState::Starving => 10,
+        }
+    }
+
+    /// The state that comes after this one
+    pub fn next(self) -> Self {
+        match self {
+            HungerState::WellFed => HungerState::Normal,
+            HungerState::Normal => HungerState::Hungry,
+            HungerState::Hungry | HungerState::Starving => HungerState::Starving,
+        }
+    }
+
+    /// Message telling the player they just got in this state
+    pub fn message(self) -> &'static str {
+        match self {
+            HungerState::WellFed => "You feel well fed.",
+            HungerState::Normal => "You are no longer well fed.",
+            HungerState::Hungry => "You are hungry.",
+            HungerState::Starving => "You are starving!",
+        }
+    }
+}
+
+/// Entity gets hungrier as turns go by
+#[derive(Component)]
+pub struct HungerClock {
+    pub state: HungerState,
+    pub turns: i32,
+}
+
+impl HungerClock {
+    /// A clock starting at `state`
+    pub fn new(state: HungerState) -> Self {
+        HungerClock {
+            state,
+            turns: state.duration(),
+        }
+    }
+}
+
+impl TimedStatus for HungerClock {
+    fn turns_mut(&mut self) -> &mut i32 {
+        &mut self.turns
+    }
+
+    fn on_expire(&mut self) -> bool {
+        *self = HungerClock::new(self.state.next());
+        true
+    }
+
+    fn expire_message(&self) -> String {
+        self.state.message().to_string()
+    }
+}
+
+/// Where an equippable item is worn
+#[derive(Deserialize, PartialEq, Eq, Copy, Clone, Debug)]
+pub enum EquipmentSlot {
+    Melee,
+    Shield,
+    Head,
+    Body,
+}
+
+/// Item can be equipped in `slot`
+#[derive(Component)]
+pub struct Equippable {
+    pub slot: EquipmentSlot,
+}
+
+/// Item is equipped by `owner` in `slot`
+#[derive(Component)]
+pub struct Equipped {
+    pub owner: Entity,
+    pub slot: EquipmentSlot,
+}
+
+/// Equipping item adds `power` to the owner's melee power
+#[derive(Component)]
+pub struct MeleePowerBonus {
+    pub power: i32,
+}
+
+/// Equipping item adds `defense` to the owner's defense
+#[derive(Component)]
+pub struct DefenseBonus {
+    pub defense: i32,
+}
+
+/// Entity wants to equip `item` from its backpack
+#[derive(Component)]
+pub struct WantsToEquip {
+    pub item: Entity,
+}
+
+/// Entity wants to put equipped `item` back in its backpack
+#[derive(Component)]
+pub struct WantsToUnequip {
+    pub item: Entity,
+}
+
+/// Entity wants to hit `target` in melee
+#[derive(Component)]
+pub struct WantsToMelee {
+    pub target: Entity,
+}
+
+/// Entity is a monster (i.e. hostile to the player)
+#[derive(Component)]
+pub struct Monster;
+
+/// Entity can be talked to, following the dialogue in `file` (relative to `assets/`)
+#[derive(Component)]
+pub struct Dialogue {
+    pub file: String,
+}
+
+/// Entity acts every time it accumulates enough energy, gaining `speed` energy per clock tick
+#[derive(Component)]
+pub struct Energy {
+    pub speed: i32,
+    pub energy: i32,
+}
+
+impl Energy {
+    pub fn new(speed: i32) -> Self {
+        Energy { speed, energy: 0 }
+    }
+}
+
+/// Entity gets to act now
+#[derive(Component)]
+pub struct MyTurn;
+
+/// Item can unlock a locked door (and gets used up doing so)
+#[derive(Component)]
+pub struct Key;
+
+/// Entity can't be seen until somebody spots it
+#[derive(Component)]
+pub struct Hidden;
+
+/// Something happens to whoever steps on the entity's tile (e.g. a trap)
+#[derive(Component)]
+pub struct EntryTrigger;
+
+/// Entry trigger that only fires once
+#[derive(Component)]
+pub struct SingleActivation;
+
+/// Entity moved to another tile this turn
+#[derive(Component)]
+pub struct EntityMoved;
+
+/// Entity takes no damage (wizard god mode)
+#[derive(Component, Default)]
+pub struct Invulnerable;
+
+/// Entity sees the whole map, walls notwithstanding (wizard mode with field of view off)
+#[derive(Component, Default)]
+pub struct Omniscient;
@@ -1,2 +1,7 @@
 pub const SCREEN_WIDTH: usize = 80;
 pub const SCREEN_HEIGHT: usize = 50;
+
+/// Rows at the bottom of the screen reserved to the UI panel
+pub const UI_HEIGHT: usize = 7;
+pub const MAP_WIDTH: usize = SCREEN_WIDTH;
+pub const MAP_HEIGHT: usize = SCREEN_HEIGHT - UI_HEIGHT;
@@ -0,0 +1,406 @@
+use crate::components as cmp;
+use crate::game_state::{player_entity, player_pos};
+use crate::gamelog::GameLog;
+use crate::input::{self, Action};
+use crate::map::level::{Level, Placement};
+use crate::map::{Map, Room, Tile};
+use crate::raws::{Raws, TemplateKind};
+use crate::spawner;
+use crate::utils::rect::Rect;
+use rltk::{Point, Rltk};
+use specs::prelude::*;
+use std::error::Error;
+use std::path::Path;
+
+/// Where edited levels are saved unless the game was started with `--level PATH`
+pub const DEFAULT_LEVEL_PATH: &str = "assets/levels/edited.ron";
+
+/// Tiles the paint tool can paint
+const TILES: [Tile; 8] = [
+    Tile::Wall,
+    Tile::Floor,
+    Tile::ClosedDoor,
+    Tile::OpenDoor,
+    Tile::LockedDoor,
+    Tile::Bookshelf,
+    Tile::ShallowWater,
+    Tile::TallGrass,
+];
+
+/// What clicking on the map does in the editor
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Tool {
+    /// Sets the clicked tile to the current tile
+    Paint,
+    /// Clicking on two walls draws a room between them, clicking on a room selects it
+    Room,
+    /// Places the current template on the clicked tile
+    Entity,
+    /// Moves the player (i.e. the level start) to the clicked tile
+    Start,
+}
+
+impl Tool {
+    /// Tool after this one (wrapping around)
+    fn next(self) -> Self {
+        match self {
+            Tool::Paint => Tool::Room,
+            Tool::Room => Tool::Entity,
+            Tool::Entity => Tool::Start,
+            Tool::Start => Tool::Paint,
+        }
+    }
+
+    /// What clicking does with this tool
+    pub fn help(self) -> &'static str {
+        match self {
+            Tool::Paint => "Click: paint tile",
+            Tool::Room => "Click: select room, or pick two corners of a new one",
+            Tool::Entity => "Click: place entity",
+            Tool::Start => "Click: move player start",
+        }
+    }
+}
+
+/// State of the level editor
+pub struct Editor {
+    pub tool: Tool,
+    /// Index of the tile painted by the paint tool (in `TILES`)
+    tile: usize,
+    /// Templates that can be placed
+    templates: Vec<String>,
+    /// Index of the template placed by the entity tool
+    template: usize,
+    /// First corner of the room being drawn
+    pub corner: Option<Point>,
+    /// Room being resized
+    pub selected: Option<usize>,
+    /// Where the level gets saved
+    pub path: String,
+}
+
+impl Editor {
+    /// New editor placing the templates in `raws` and saving levels to `path`
+    pub fn new(raws: &Raws, path: &str) -> Self {
+        let kinds = [
+            TemplateKind::Mob,
+            TemplateKind::Item,
+            TemplateKind::Npc,
+            TemplateKind::Trap,
+        ];
+        let templates = kinds
+            .iter()
+            .flat_map(|&kind| raws.names_of_kind(kind))
+            .map(String::from)
+            .collect();
+
+        Editor {
+            tool: Tool::Paint,
+            tile: 0,
+            templates,
+            template: 0,
+            corner: None,
+            selected: None,
+            path: path.to_string(),
+        }
+    }
+
+    /// What the current tool places (if anything)
+    pub fn brush(&self) -> Option<&str> {
+        match self.tool {
+            Tool::Paint => Some(TILES[self.tile].name()),
+            Tool::Entity => self.templates.get(self.template).map(String::as_str),
+            Tool::Room | Tool::Start => None,
+        }
+    }
+
+    /// Switch to the next (or previous if `forward` is false) brush of the current tool
+    fn cycle_brush(&mut self, forward: bool) {
+        let (current, len) = match self.tool {
+            Tool::Paint => (&mut self.tile, TILES.len()),
+            Tool::Entity => (&mut self.template, self.templates.len()),
+            Tool::Room | Tool::Start => return,
+        };
+        if len > 0 {
+            *current = if forward {
+                (*current + 1) % len
+            } else {
+                (*current + len - 1) % len
+            };
+        }
+    }
+}
+
+/// Room with corners `a` and `b`
+fn room_between(a: Point, b: Point) -> Room {
+    Room {
+        x: a.x.min(b.x),
+        y: a.y.min(b.y),
+        width: (a.x - b.x).abs() + 1,
+        height: (a.y - b.y).abs() + 1,
+    }
+}
+
+/// Uses the current tool on the tile at `target`
+fn click(ecs: &mut World, target: Point) {
+    if !ecs.fetch::<Map>().contains_point(target.x, target.y) {
+        return;
+    }
+
+    let tool = ecs.fetch::<Editor>().tool;
+    match tool {
+        Tool::Paint => {
+            let tile = TILES[ecs.fetch::<Editor>().tile];
+            let mut map = ecs.fetch_mut::<Map>();
+            *map.at_mut(target.x, target.y) = tile;
+            map.compute_regions();
+        }
+        Tool::Room => {
+            let mut editor = ecs.fetch_mut::<Editor>();
+            let mut map = ecs.fetch_mut::<Map>();
+            if let Some(id) = map.room_at(target) {
+                editor.selected = Some(id);
+                editor.corner = None;
+                return;
+            }
+
+            let corner = match editor.corner.take() {
+                Some(corner) => corner,
+                None => {
+                    editor.corner = Some(target);
+                    editor.selected = None;
+                    return;
+                }
+            };
+            match map.try_add_room(room_between(corner, target)) {
+                Ok(()) => {
+                    let id = map.rooms.len() - 1;
+                    map.light_room(id);
+                    map.compute_regions();
+                    editor.selected = Some(id);
+                }
+                Err(reason) => ecs
+                    .fetch_mut::<GameLog>()
+                    .log(format!("Can't place room: {:?}", reason)),
+            }
+        }
+        Tool::Entity => {
+            let name = ecs.fetch::<Editor>().brush().map(String::from);
+            if let Some(name) = name {
+                spawner::spawn_named(ecs, &name, target);
+            }
+        }
+        Tool::Start => {
+            if !ecs.fetch::<Map>().at(target.x, target.y).is_passable() {
+                ecs.fetch_mut::<GameLog>()
+                    .log("The player can't start there.");
+                return;
+            }
+            let player = player_entity(ecs);
+            ecs.write_storage::<cmp::Pos>()
+                .insert(player, cmp::Pos::from(target))
+                .expect("Unable to move player");
+        }
+    }
+}
+
+/// Grows (or shrinks) the selected room `dx` tiles east and `dy` tiles south
+fn resize_selected(ecs: &mut World, dx: i32, dy: i32) {
+    let id = match ecs.fetch::<Editor>().selected {
+        Some(id) => id,
+        None => return,
+    };
+
+    let mut map = ecs.fetch_mut::<Map>();
+    let mut room = map.rooms[id].clone();
+    room.width += dx;
+    room.height += dy;
+    if room.width < 1 || room.height < 1 {
+        return;
+    }
+    match map.resize_room(id, room) {
+        Ok(()) => map.compute_regions(),
+        Err(reason) => ecs
+            .fetch_mut::<GameLog>()
+            .log(format!("Can't resize room: {:?}", reason)),
+    }
+}
+
+/// Removes the selected room (with the room tool) or whatever is on the tile at `target`
+/// (except for the player)
+fn delete(ecs: &mut World, target: Point) {
+    let selected = {
+        let mut editor = ecs.fetch_mut::<Editor>();
+        if editor.tool == Tool::Room {
+            editor.selected.take()
+        } else {
+            None
+        }
+    };
+    if let Some(id) = selected {
+        let mut map = ecs.fetch_mut::<Map>();
+        map.remove_room(id);
+        map.compute_regions();
+        return;
+    }
+
+    let player = player_entity(ecs);
+    let doomed: Vec<Entity> = {
+        let entities = ecs.entities();
+        let positions = ecs.read_storage::<cmp::Pos>();
+        (&entities, &positions)
+            .join()
+            .filter(|(e, pos)| *e != player && pos.x == target.x && pos.y == target.y)
+            .map(|(e, _)| e)
+            .collect()
+    };
+    for entity in doomed {
+        ecs.delete_entity(entity).expect("Unable to delete entity");
+    }
+    ecs.maintain();
+}
+
+/// The level being edited: the map, the player's position and every entity built from a
+/// template lying on the map
+fn current_level(ecs: &World) -> Level {
+    let start = player_pos(ecs);
+    let player = player_entity(ecs);
+    let raws = ecs.fetch::<Raws>();
+    let entities = ecs.entities();
+    let names = ecs.read_storage::<cmp::Name>();
+    let positions = ecs.read_storage::<cmp::Pos>();
+
+    Level {
+        map: Map::clone(&ecs.fetch::<Map>()),
+        start: Some((start.x, start.y)),
+        entities: (&entities, &names, &positions)
+            .join()
+            .filter(|(e, name, _)| *e != player && raws.get(&name.name).is_some())
+            .map(|(_, name, pos)| Placement {
+                template: name.name.clone(),
+                x: pos.x,
+                y: pos.y,
+            })
+            .collect(),
+    }
+}
+
+/// Saves the level being edited to `path` (creating its directory if needed)
+fn save_level(ecs: &World, path: &str) -> Result<(), Box<dyn Error>> {
+    if let Some(dir) = Path::new(path).parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    current_level(ecs).save(path)
+}
+
+/// Saves the level being edited where the editor says, telling how it went
+fn save(ecs: &World) {
+    let path = ecs.fetch::<Editor>().path.clone();
+    let msg = match save_level(ecs, &path) {
+        Ok(()) => format!("Level saved to {}.", path),
+        Err(e) => format!("Unable to save level: {}", e),
+    };
+    ecs.fetch_mut::<GameLog>().log(msg);
+}
+
+/// Handles input in the level editor. Returns whether to keep editing.
+pub fn input(ecs: &mut World, ctx: &Rltk) -> bool {
+    if ctx.left_click {
+        click(ecs, ctx.mouse_point());
+        return true;
+    }
+
+    let action = match input::pressed_action(ecs, ctx) {
+        None => return true,
+        Some(action) => action,
+    };
+
+    if let Some(dir) = action.move_dir() {
+        let (dx, dy) = dir.delta();
+        resize_selected(ecs, dx, dy);
+        return true;
+    }
+
+    match action {
+        Action::LevelEditor => return false,
+        // Cancel what's going on first.
+        Action::Cancel => {
+            let mut editor = ecs.fetch_mut::<Editor>();
+            if editor.corner.is_none() && editor.selected.is_none() {
+                return false;
+            }
+            editor.corner = None;
+            editor.selected = None;
+        }
+        Action::NextTool => {
+            let mut editor = ecs.fetch_mut::<Editor>();
+            editor.tool = editor.tool.next();
+            editor.corner = None;
+            editor.selected = None;
+        }
+        Action::NextBrush => ecs.fetch_mut::<Editor>().cycle_brush(true),
+        Action::PreviousBrush => ecs.fetch_mut::<Editor>().cycle_brush(false),
+        Action::Delete => delete(ecs, ctx.mouse_point()),
+        Action::Save => save(ecs),
+        _ => {}
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rooms_are_drawn_between_any_corners() {
+        let room = Room {
+            x: 2,
+            y: 3,
+            width: 4,
+            height: 2,
+        };
+
+        assert_eq!(room_between(Point::new(2, 3), Point::new(5, 4)), room);
+        assert_eq!(room_between(Point::new(5, 3), Point::new(2, 4)), room);
+        assert_eq!(room_between(Point::new(5, 4), Point::new(2, 3)), room);
+    }
+
+    #[test]
+    fn current_level_survives_ron() {
+        let mut ecs = World::new();
+        ecs.register::<cmp::Pos>();
+        ecs.register::<cmp::Name>();
+        ecs.register::<cmp::Player>();
+        ecs.insert(Raws::parse(include_str!("../assets/raws/spawns.json")).unwrap());
+        let mut map = Map::all(6, 5, Tile::Floor);
+        *map.at_mut(0, 0) = Tile::LockedDoor;
+        ecs.insert(map);
+
+        let place = |ecs: &mut World, name: &str, x, y| {
+            ecs.create_entity()
+                .with(cmp::Name::from(name))
+                .with(cmp::Pos::from(Point::new(x, y)))
+                .build()
+        };
+        let player = place(&mut ecs, "Player", 1, 2);
+        ecs.write_storage::<cmp::Player>()
+            .insert(player, cmp::Player)
+            .unwrap();
+        place(&mut ecs, "Health Potion", 3, 3);
+        place(&mut ecs, "Not a template", 4, 1);
+
+        let src = ron::ser::to_string(&current_level(&ecs)).unwrap();
+        let level: Level = ron::de::from_str(&src).unwrap();
+
+        assert_eq!(level.map.tiles, ecs.fetch::<Map>().tiles);
+        assert_eq!(level.start, Some((1, 2)));
+        assert_eq!(
+            level.entities,
+            vec![Placement {
+                template: "Health Potion".to_string(),
+                x: 3,
+                y: 3,
+            }]
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use crate::components as cmp;
+use specs::prelude::*;
+
+/// Something that can happen to an entity (e.g. because it was hit by a fireball)
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Effect {
+    /// Restore some hit points
+    Heal(i32),
+    /// Lose some hit points
+    Damage(i32),
+    /// Be confused for some turns
+    Confuse(i32),
+    /// Be blind for some turns
+    Blind(i32),
+    /// Lose `damage` hit points per turn for some turns
+    Poison { turns: i32, damage: i32 },
+    /// Restore `amount` hit points per turn for some turns
+    Regenerate { turns: i32, amount: i32 },
+    /// Stop being hungry
+    Feed,
+    /// Be moved to a random spot of the map
+    Teleport,
+}
+
+/// An effect waiting to be applied to `target`
+pub struct EffectEvent {
+    pub target: Entity,
+    pub effect: Effect,
+}
+
+/// Effects waiting to be applied (by `systems::ApplyEffects`)
+#[derive(Default)]
+pub struct EffectQueue {
+    events: Vec<EffectEvent>,
+}
+
+impl EffectQueue {
+    /// Schedule `effect` to be applied to `target`
+    pub fn push(&mut self, target: Entity, effect: Effect) {
+        self.events.push(EffectEvent { target, effect });
+    }
+
+    /// Take all pending effects out of the queue
+    pub fn drain(&mut self) -> Vec<EffectEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+/// Components that make things (e.g. items or traps) cause effects
+pub type EffectSources<'a> = (
+    ReadStorage<'a, cmp::ProvidesHealing>,
+    ReadStorage<'a, cmp::InflictsDamage>,
+    ReadStorage<'a, cmp::Confusion>,
+    ReadStorage<'a, cmp::Teleports>,
+    ReadStorage<'a, cmp::Blinds>,
+    ReadStorage<'a, cmp::InflictsPoison>,
+    ReadStorage<'a, cmp::ProvidesRegeneration>,
+    ReadStorage<'a, cmp::ProvidesFood>,
+);
+
+/// Effects `source` causes on whoever it is used on (or whoever triggers it)
+pub fn effects_of(sources: &EffectSources, source: Entity) -> Vec<Effect> {
+    let (healings, damages, confusions, teleports, blinds, poisons, regenerations, foods) = sources;
+
+    let mut effects = vec![];
+    if let Some(healing) = healings.get(source) {
+        effects.push(Effect::Heal(healing.heal_amount));
+    }
+    if let Some(damage) = damages.get(source) {
+        effects.push(Effect::Damage(damage.damage));
+    }
+    if let Some(confusion) = confusions.get(source) {
+        effects.push(Effect::Confuse(confusion.turns));
+    }
+    if teleports.contains(source) {
+        effects.push(Effect::Teleport);
+    }
+    if let Some(blind) = blinds.get(source) {
+        effects.push(Effect::Blind(blind.turns));
+    }
+    if let Some(poison) = poisons.get(source) {
+        effects.push(Effect::Poison {
+            turns: poison.turns,
+            damage: poison.damage,
+        });
+    }
+    if let Some(regen) = regenerations.get(source) {
+        effects.push(Effect::Regenerate {
+            turns: regen.turns,
+            amount: regen.amount,
+        });
+    }
+    if foods.contains(source) {
+        effects.push(Effect::Feed);
+    }
+    effects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_effect_component_is_mapped() {
+        let mut world = World::new();
+        EffectSources::setup(&mut world);
+        let trap = world
+            .create_entity()
+            .with(cmp::ProvidesHealing { heal_amount: 4 })
+            .with(cmp::ProvidesRegeneration {
+                turns: 3,
+                amount: 1,
+            })
+            .with(cmp::ProvidesFood)
+            .build();
+
+        let effects = effects_of(&world.system_data(), trap);
+
+        assert_eq!(
+            effects,
+            vec![
+                Effect::Heal(4),
+                Effect::Regenerate {
+                    turns: 3,
+                    amount: 1
+                },
+                Effect::Feed
+            ]
+        );
+    }
+}
@@ -1,70 +1,661 @@
 use crate::components as cmp;
-use crate::systems as sys;
+use crate::editor;
+use crate::gamelog::GameLog;
+use crate::gui::{self, MenuResult};
+use crate::input::{self, Action};
+use crate::map::history::History;
+use crate::map::pathfinding::Pathfinder;
 use crate::map::{Map, Tile};
-use rltk::{Console, GameState, Rltk};
+use crate::systems as sys;
+use crate::systems::initiative::{
+    run_bounded, Clock, ATTACK_COST, DROP_COST, EQUIP_COST, MOVE_COST, PICKUP_COST, USE_ITEM_COST,
+    WAIT_COST,
+};
+use crate::systems::inventory::name_of;
+use crate::utils::dir::Dir;
+use crate::utils::rect::Rect;
+use crate::wizard;
+use log::info;
+use rand::seq::SliceRandom;
+use rltk::{Console, GameState, Point, Rltk};
 use single::Single;
 use specs::prelude::*;
 
 /******************/
 /* Helper methods */
 /******************/
-/// Moves player keeping him/her within the world bounds
-fn try_move_player(delta_x: i32, delta_y: i32, world: &mut World) {
-    // Get player along with their position
-    let positions = &mut world.write_storage::<cmp::Pos>();
-    let players = &world.read_storage::<cmp::Player>();
-    let (mut player_pos, _) = (positions, players)
+/// Moves player keeping him/her within the world bounds (attacking whoever is in the way).
+/// Returns the energy spent doing so (if the player did anything at all).
+fn try_move_player(delta_x: i32, delta_y: i32, world: &mut World) -> Option<i32> {
+    let player = player_entity(world);
+    let here = player_pos(world);
+
+    // Confused players stumble around randomly (which takes a turn even if they bump into
+    // something)
+    let confused = world.read_storage::<cmp::Confused>().contains(player);
+    let (delta_x, delta_y) = if confused {
+        world.fetch_mut::<GameLog>().log("You stumble around...");
+        let map = world.fetch::<Map>();
+        let dirs: Vec<Dir> = Dir::all().filter(|&d| map.allows_step(here, d)).collect();
+        match dirs.choose(&mut rand::thread_rng()) {
+            Some(dir) => dir.delta(),
+            None => return Some(MOVE_COST),
+        }
+    } else {
+        (delta_x, delta_y)
+    };
+    let blocked = if confused { Some(MOVE_COST) } else { None };
+
+    // Nothing can be done in directions the movement rules forbid (e.g. diagonals)
+    let dir = Dir::from_delta((delta_x, delta_y))?;
+    if !world.fetch::<Map>().allows_step(here, dir) {
+        return None;
+    }
+    let (x, y) = (here.x + delta_x, here.y + delta_y);
+
+    // Attack instead of moving if there's someone to fight at the destination
+    let target = {
+        let entities = world.entities();
+        let positions = world.read_storage::<cmp::Pos>();
+        let stats = world.read_storage::<cmp::CombatStats>();
+        (&entities, &positions, &stats)
+            .join()
+            .find(|(e, pos, _)| *e != player && pos.x == x && pos.y == y)
+            .map(|(e, _, _)| e)
+    };
+    if let Some(target) = target {
+        world
+            .write_storage::<cmp::WantsToMelee>()
+            .insert(player, cmp::WantsToMelee { target })
+            .expect("Unable to insert want to melee");
+        return Some(ATTACK_COST);
+    }
+
+    // Bumping into doors opens them (locked ones need a key).
+    let door = world.fetch::<Map>().at(x, y);
+    match door {
+        Tile::ClosedDoor => {
+            *world.fetch_mut::<Map>().at_mut(x, y) = Tile::OpenDoor;
+            world.fetch_mut::<GameLog>().log("You open the door.");
+            return Some(MOVE_COST);
+        }
+        Tile::LockedDoor => return try_unlock_door(world, x, y).or(blocked),
+        _ => {}
+    }
+
+    // Don't move player where the movement rules don't allow it (e.g. onto walls)
+    if world.fetch::<Map>().step_cost(here, dir).is_none() {
+        return blocked;
+    }
+
+    // Move him
+    let mut positions = world.write_storage::<cmp::Pos>();
+    let pos = positions
+        .get_mut(player)
+        .expect("Trying to move unexistent player!");
+    pos.x = x;
+    pos.y = y;
+    world
+        .write_storage::<cmp::EntityMoved>()
+        .insert(player, cmp::EntityMoved)
+        .expect("Unable to insert entity moved");
+    Some(MOVE_COST)
+}
+
+/// Makes the player unlock the door at (`x`, `y`) with a key from his/her backpack (using it up).
+/// Returns the energy spent doing so (if the player had a key at all).
+fn try_unlock_door(world: &mut World, x: i32, y: i32) -> Option<i32> {
+    let player = player_entity(world);
+    let key = {
+        let entities = world.entities();
+        let keys = world.read_storage::<cmp::Key>();
+        let backpacks = world.read_storage::<cmp::InBackpack>();
+        (&entities, &keys, &backpacks)
+            .join()
+            .find(|(_, _, backpack)| backpack.owner == player)
+            .map(|(key, _, _)| key)
+    };
+
+    let key = match key {
+        Some(key) => key,
+        None => {
+            world.fetch_mut::<GameLog>().log("The door is locked.");
+            return None;
+        }
+    };
+
+    let name = name_of(&world.read_storage::<cmp::Name>(), key);
+    world.delete_entity(key).expect("Unable to use up key");
+    *world.fetch_mut::<Map>().at_mut(x, y) = Tile::OpenDoor;
+    world
+        .fetch_mut::<GameLog>()
+        .log(format!("You unlock the door with the {}.", name));
+    Some(MOVE_COST)
+}
+
+/// Directions of the open doors next to the player that can be closed (i.e. with nothing in the
+/// way)
+fn closable_doors(world: &World) -> Vec<Dir> {
+    let here = player_pos(world);
+    let map = world.fetch::<Map>();
+    let positions = world.read_storage::<cmp::Pos>();
+    Dir::all()
+        .filter(|&dir| map.allows_step(here, dir))
+        .filter(|&dir| {
+            let (dx, dy) = dir.delta();
+            let (x, y) = (here.x + dx, here.y + dy);
+            map.at(x, y) == Tile::OpenDoor && !positions.join().any(|p| p.x == x && p.y == y)
+        })
+        .collect()
+}
+
+/// Makes the player close the door in direction `dir` (which must be closable)
+fn close_door(world: &mut World, dir: Dir) {
+    let here = player_pos(world);
+    let (dx, dy) = dir.delta();
+    *world.fetch_mut::<Map>().at_mut(here.x + dx, here.y + dy) = Tile::ClosedDoor;
+    world.fetch_mut::<GameLog>().log("You close the door.");
+}
+
+/// Get the player's current position
+pub fn player_pos(world: &World) -> Point {
+    let positions = world.read_storage::<cmp::Pos>();
+    let players = world.read_storage::<cmp::Player>();
+    let (pos, _) = (&positions, &players)
         .join()
         .single()
-        .expect("Trying to move unexistent player!");
+        .expect("Trying to find unexistent player!");
+
+    Point::new(pos.x, pos.y)
+}
 
-    // Calculate new hypothetical player position
-    let new_x = player_pos.x + delta_x;
-    let new_y = player_pos.y + delta_y;
+/// Get the player entity
+pub fn player_entity(world: &World) -> Entity {
+    let entities = world.entities();
+    let players = world.read_storage::<cmp::Player>();
+    let (player, _) = (&entities, &players)
+        .join()
+        .single()
+        .expect("Trying to find unexistent player!");
 
-    // Don't move player onto walls
-    let mp = world.fetch::<Map>();
-    if mp.at(new_x, new_y) == Tile::Wall {
+    player
+}
+
+/// Makes the player try to pick up whatever item lies under him/her.
+/// Returns whether there was something to pick up (i.e. the player took a turn).
+fn try_pickup_item(world: &mut World) -> bool {
+    let player = player_entity(world);
+    let player_pos = player_pos(world);
+
+    let item = {
+        let entities = world.entities();
+        let items = world.read_storage::<cmp::Item>();
+        let positions = world.read_storage::<cmp::Pos>();
+        (&entities, &items, &positions)
+            .join()
+            .find(|(_, _, pos)| pos.x == player_pos.x && pos.y == player_pos.y)
+            .map(|(item, _, _)| item)
+    };
+
+    match item {
+        None => {
+            world
+                .fetch_mut::<GameLog>()
+                .log("There is nothing here to pick up.");
+            false
+        }
+        Some(item) => {
+            world
+                .write_storage::<cmp::WantsToPickup>()
+                .insert(player, cmp::WantsToPickup { item })
+                .expect("Unable to insert want to pickup");
+            true
+        }
+    }
+}
+
+/// Checks if the player can see any monster
+fn hostile_in_view(world: &World) -> bool {
+    let player = player_entity(world);
+    let viewsheds = world.read_storage::<cmp::Viewshed>();
+    let visible = match viewsheds.get(player) {
+        Some(viewshed) => &viewshed.visible_tiles,
+        None => return false,
+    };
+    let positions = world.read_storage::<cmp::Pos>();
+    let monsters = world.read_storage::<cmp::Monster>();
+
+    (&positions, &monsters)
+        .join()
+        .any(|(pos, _)| visible.contains(&Point::new(pos.x, pos.y)))
+}
+
+/// Next tile the player should walk on while travelling (if there's anywhere left to go)
+fn autotravel_step(world: &World, travel: Autotravel) -> Option<Point> {
+    let here = player_pos(world);
+    let map = world.fetch::<Map>();
+    let mut pathfinder = world.fetch_mut::<Pathfinder>();
+
+    match travel {
+        Autotravel::Explore => {
+            let unexplored: Vec<Point> = (0..map.tiles.len())
+                .filter(|&idx| !map.revealed[idx] && map.tiles[idx].is_passable())
+                .map(|idx| map.idx_xy(idx))
+                .collect();
+            pathfinder.step_towards(&map, &unexplored, here)
+        }
+        Autotravel::To(dest) if dest == here => None,
+        Autotravel::To(dest) => pathfinder
+            .path(&map, here, dest)
+            .and_then(|path| path.first().copied()),
+    }
+}
+
+/// Makes the player walk one step on their own (stopping on any key press or enemy sighting)
+fn autotravel_input(gs: &mut State, ctx: &mut Rltk, travel: Autotravel) {
+    if ctx.key.is_some() || ctx.left_click {
+        gs.autotravel = None;
+        return;
+    }
+    if hostile_in_view(&gs.ecs) {
+        gs.ecs
+            .fetch_mut::<GameLog>()
+            .log("You spot an enemy and stop.");
+        gs.autotravel = None;
         return;
     }
 
-    // Move him
-    use std::cmp::{max, min};
-    player_pos.x = max(0, min((mp.get_width() - 1) as i32, player_pos.x + delta_x));
-    player_pos.y = max(0, min((mp.get_height() - 1) as i32, player_pos.y + delta_y));
+    let step = match autotravel_step(&gs.ecs, travel) {
+        Some(step) => step,
+        None => {
+            if travel == Autotravel::Explore {
+                gs.ecs
+                    .fetch_mut::<GameLog>()
+                    .log("There is nothing left to explore.");
+            }
+            gs.autotravel = None;
+            return;
+        }
+    };
+
+    let here = player_pos(&gs.ecs);
+    match try_move_player(step.x - here.x, step.y - here.y, &mut gs.ecs) {
+        Some(cost) => end_player_turn(gs, cost),
+        None => gs.autotravel = None,
+    }
+}
+
+/// Ends the player's turn, spending `cost` energy, and lets the rest of the world act
+fn end_player_turn(gs: &mut State, cost: i32) {
+    let player = player_entity(&gs.ecs);
+    if let Some(energy) = gs.ecs.write_storage::<cmp::Energy>().get_mut(player) {
+        energy.energy -= cost;
+    }
+    gs.ecs.write_storage::<cmp::MyTurn>().remove(player);
+    gs.runstate = RunState::Ticking;
 }
 
 /// Handles player input
 fn player_input(gs: &mut State, ctx: &mut Rltk) {
+    // Clicking on a known tile makes the player travel there.
+    if ctx.left_click {
+        let dest = ctx.mouse_point();
+        let map = gs.ecs.fetch::<Map>();
+        if map.contains_point(dest.x, dest.y) {
+            let idx = map.xy_idx(dest.x, dest.y);
+            if map.revealed[idx] && map.tiles[idx].is_passable() {
+                gs.autotravel = Some(Autotravel::To(dest));
+            }
+        }
+        return;
+    }
+
+    let action = match input::pressed_action(&gs.ecs, ctx) {
+        None => return,
+        Some(action) => action,
+    };
+
     // Player movement
-    {
-        use rltk::VirtualKeyCode::*;
-        match ctx.key {
-            None => {}
-            Some(key) => match key {
-                Left => try_move_player(-1, 0, &mut gs.ecs),
-                Right => try_move_player(1, 0, &mut gs.ecs),
-                Up => try_move_player(0, -1, &mut gs.ecs),
-                Down => try_move_player(0, 1, &mut gs.ecs),
-                _ => {}
-            },
+    if let Some(dir) = action.move_dir() {
+        let (dx, dy) = dir.delta();
+        if let Some(cost) = try_move_player(dx, dy, &mut gs.ecs) {
+            end_player_turn(gs, cost);
+        }
+        return;
+    }
+
+    match action {
+        // Enter look mode with cursor on top of the player.
+        Action::Look => {
+            gs.runstate = RunState::Look {
+                cursor: player_pos(&gs.ecs),
+            }
+        }
+        Action::Wait => end_player_turn(gs, WAIT_COST),
+        Action::AutoExplore => {
+            if hostile_in_view(&gs.ecs) {
+                gs.ecs
+                    .fetch_mut::<GameLog>()
+                    .log("Not with enemies in sight!");
+            } else {
+                gs.autotravel = Some(Autotravel::Explore);
+            }
+        }
+        Action::PickUp => {
+            if try_pickup_item(&mut gs.ecs) {
+                end_player_turn(gs, PICKUP_COST);
+            }
         }
+        // Ask which door to close when there are several.
+        Action::CloseDoor => match closable_doors(&gs.ecs).as_slice() {
+            [] => gs
+                .ecs
+                .fetch_mut::<GameLog>()
+                .log("There is no door to close here."),
+            &[dir] => {
+                close_door(&mut gs.ecs, dir);
+                end_player_turn(gs, MOVE_COST);
+            }
+            _ => {
+                gs.ecs
+                    .fetch_mut::<GameLog>()
+                    .log("Close which door? (pick a direction)");
+                gs.runstate = RunState::CloseDoor;
+            }
+        },
+        Action::Inventory => gs.runstate = RunState::ShowInventory,
+        Action::Drop => gs.runstate = RunState::ShowDropItem,
+        Action::Equipment => gs.runstate = RunState::ShowEquipment,
+        Action::ReplayGeneration => {
+            if gs.ecs.fetch::<History>().is_empty() {
+                gs.ecs
+                    .fetch_mut::<GameLog>()
+                    .log("No map generation was recorded (start with --record-gen).");
+            } else {
+                gs.runstate = RunState::ReplayGeneration {
+                    step: 0,
+                    paused: false,
+                };
+            }
+        }
+        Action::LevelEditor => gs.runstate = RunState::LevelEditor,
+        Action::WizardConsole => gs.runstate = RunState::WizardConsole,
+        // TODO: implement this once stairs exist.
+        Action::Descend => {
+            info!("Action {:?} not available yet", action);
+        }
+        _ => {}
     }
 }
 
+/// Handles input while replaying the map generation (advancing it if not paused)
+fn replay_input(gs: &mut State, ctx: &mut Rltk, step: usize, paused: bool) {
+    let last = gs.ecs.fetch::<History>().len().saturating_sub(1);
+    let (step, paused) = match input::pressed_action(&gs.ecs, ctx) {
+        Some(Action::Cancel) | Some(Action::ReplayGeneration) => {
+            gs.runstate = RunState::Running;
+            return;
+        }
+        Some(Action::Confirm) => (step, !paused),
+        Some(Action::MoveWest) => (step.saturating_sub(1), true),
+        Some(Action::MoveEast) => (step + 1, true),
+        _ if paused => (step, paused),
+        // One step per frame, stopping on the finished map.
+        _ => (step + 1, step + 1 >= last),
+    };
+
+    gs.runstate = RunState::ReplayGeneration {
+        step: std::cmp::min(step, last),
+        paused,
+    };
+}
+
+/// Handles input while in look mode (moves the cursor around)
+fn look_input(gs: &mut State, ctx: &mut Rltk, cursor: Point) {
+    let action = match input::pressed_action(&gs.ecs, ctx) {
+        None => return,
+        Some(action) => action,
+    };
+
+    if let Action::Look | Action::Cancel = action {
+        gs.runstate = RunState::Running;
+        return;
+    }
+
+    // Keep the cursor inside the map.
+    if let Some(dir) = action.move_dir() {
+        let (dx, dy) = dir.delta();
+        let new_cursor = Point::new(cursor.x + dx, cursor.y + dy);
+        if gs
+            .ecs
+            .fetch::<Map>()
+            .contains_point(new_cursor.x, new_cursor.y)
+        {
+            gs.runstate = RunState::Look { cursor: new_cursor };
+        }
+    }
+}
+
+/// Handles input while picking which door to close
+fn close_door_input(gs: &mut State, ctx: &mut Rltk) {
+    let action = match input::pressed_action(&gs.ecs, ctx) {
+        None => return,
+        Some(action) => action,
+    };
+
+    if let Some(dir) = action.move_dir() {
+        if closable_doors(&gs.ecs).contains(&dir) {
+            close_door(&mut gs.ecs, dir);
+            end_player_turn(gs, MOVE_COST);
+            return;
+        }
+        gs.ecs
+            .fetch_mut::<GameLog>()
+            .log("There is no door to close there.");
+    } else if action != Action::Cancel {
+        return;
+    }
+    gs.runstate = RunState::Running;
+}
+
+/// Handles input while aiming `item` (which has range `range`) at something
+fn targeting_input(gs: &mut State, ctx: &mut Rltk, item: Entity, range: i32, cursor: Point) {
+    // Clicking on a tile aims at it straight away.
+    let target = if ctx.left_click {
+        ctx.mouse_point()
+    } else {
+        let action = match input::pressed_action(&gs.ecs, ctx) {
+            None => return,
+            Some(action) => action,
+        };
+
+        if action == Action::Cancel {
+            gs.runstate = RunState::Running;
+            return;
+        }
+
+        if let Some(dir) = action.move_dir() {
+            let (dx, dy) = dir.delta();
+            let new_cursor = Point::new(cursor.x + dx, cursor.y + dy);
+            if gs
+                .ecs
+                .fetch::<Map>()
+                .contains_point(new_cursor.x, new_cursor.y)
+            {
+                gs.runstate = RunState::ShowTargeting {
+                    item,
+                    range,
+                    cursor: new_cursor,
+                };
+            }
+            return;
+        }
+
+        if action != Action::Confirm {
+            return;
+        }
+        cursor
+    };
+
+    if !gui::is_targetable(&gs.ecs, range, target) {
+        gs.ecs
+            .fetch_mut::<GameLog>()
+            .log("You can't target that tile.");
+        return;
+    }
+
+    let player = player_entity(&gs.ecs);
+    gs.ecs
+        .write_storage::<cmp::WantsToUseItem>()
+        .insert(
+            player,
+            cmp::WantsToUseItem {
+                item,
+                target: Some(target),
+            },
+        )
+        .expect("Unable to insert want to use item");
+    end_player_turn(gs, USE_ITEM_COST);
+}
+
 /************************/
 /* Game state structure */
 /************************/
+/// What the game is currently doing (and thus how input is interpreted)
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum RunState {
+    /// Normal gameplay (waiting for the player to do something)
+    Running,
+    /// The world is running until it's the player's turn again
+    Ticking,
+    /// Looking around with a keyboard driven cursor
+    Look { cursor: Point },
+    /// Picking which of the doors next to the player to close
+    CloseDoor,
+    /// Browsing the player's backpack
+    ShowInventory,
+    /// Choosing an item to drop
+    ShowDropItem,
+    /// Browsing (and taking off) the player's equipment
+    ShowEquipment,
+    /// Aiming `item` at some tile at most `range` tiles away from the player
+    ShowTargeting {
+        item: Entity,
+        range: i32,
+        cursor: Point,
+    },
+    /// The player died
+    GameOver,
+    /// Replaying the generation of the current map (debug tool)
+    ReplayGeneration { step: usize, paused: bool },
+    /// Editing the current level (debug tool)
+    LevelEditor,
+    /// Typing commands in the wizard console (debug tool)
+    WizardConsole,
+}
+
+/// Where the player is walking on their own (one step per turn)
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Autotravel {
+    /// Towards the nearest unexplored tile
+    Explore,
+    /// Along the shortest path to a tile
+    To(Point),
+}
+
 pub struct State {
     pub ecs: World,
+    pub runstate: RunState,
+    /// Set while the player is exploring/travelling automatically
+    pub autotravel: Option<Autotravel>,
 }
 
 impl State {
+    /// Lets the world (i.e. everyone but the player) act until it's the player's turn again
+    fn advance_world(&mut self) {
+        // Resolve what the player just did before anybody else acts.
+        self.run_systems();
+
+        // Bounded, so that a broken schedule can't freeze the game.
+        run_bounded(|| {
+            if self.runstate == RunState::GameOver {
+                return true;
+            }
+
+            let mut scheduler = sys::Scheduler;
+            scheduler.run_now(&self.ecs);
+
+            // Process the game turns that went by in the meantime.
+            let pending_turns = std::mem::take(&mut self.ecs.fetch_mut::<Clock>().pending_turns);
+            for _ in 0..pending_turns {
+                self.run_turn_systems();
+            }
+
+            let player = player_entity(&self.ecs);
+            if self.ecs.read_storage::<cmp::MyTurn>().contains(player) {
+                self.runstate = RunState::Running;
+                return true;
+            }
+
+            let mut ai = sys::AI;
+            ai.run_now(&self.ecs);
+            self.run_systems();
+            false
+        });
+    }
+
+    /// Runs the systems that should only run once per game turn
+    fn run_turn_systems(&mut self) {
+        let mut status_effects = sys::StatusEffects;
+        status_effects.run_now(&self.ecs);
+        let mut perception = sys::Perception;
+        perception.run_now(&self.ecs);
+        sys::TickStatus::<cmp::Confused>::default().run_now(&self.ecs);
+        sys::TickStatus::<cmp::Blind>::default().run_now(&self.ecs);
+        sys::TickStatus::<cmp::Poisoned>::default().run_now(&self.ecs);
+        sys::TickStatus::<cmp::Regenerating>::default().run_now(&self.ecs);
+        sys::TickStatus::<cmp::HungerClock>::default().run_now(&self.ecs);
+    }
+
     fn run_systems(&mut self) {
+        let mut lighting = sys::Lighting;
+        lighting.run_now(&self.ecs);
         let mut vis = sys::Visibility;
         vis.run_now(&self.ecs);
+        let mut path_indexing = sys::PathIndexing;
+        path_indexing.run_now(&self.ecs);
+        let mut pickup = sys::ItemCollection;
+        pickup.run_now(&self.ecs);
+        let mut drop = sys::ItemDrop;
+        drop.run_now(&self.ecs);
+        let mut equip = sys::ItemEquip;
+        equip.run_now(&self.ecs);
+        let mut unequip = sys::ItemUnequip;
+        unequip.run_now(&self.ecs);
+        let mut item_use = sys::ItemUse;
+        item_use.run_now(&self.ecs);
+        let mut melee = sys::MeleeCombat;
+        melee.run_now(&self.ecs);
+        let mut traps = sys::TrapTrigger;
+        traps.run_now(&self.ecs);
+        let mut effects = sys::ApplyEffects;
+        effects.run_now(&self.ecs);
+        let mut reaper = sys::Reaper;
+        reaper.run_now(&self.ecs);
 
         self.ecs.maintain();
+
+        // The player doesn't get reaped: the game just ends.
+        let player = player_entity(&self.ecs);
+        let player_dead = self
+            .ecs
+            .read_storage::<cmp::CombatStats>()
+            .get(player)
+            .map_or(false, |stats| stats.hp <= 0);
+        if player_dead {
+            self.ecs.fetch_mut::<GameLog>().log("You die...");
+            self.runstate = RunState::GameOver;
+        }
     }
 
     // TODO: find a way to make this work
@@ -101,29 +692,172 @@ impl GameState for State {
         // Clear screen.
         ctx.cls();
 
+        // Generation replays take over the whole screen (and stop the game).
+        if let RunState::ReplayGeneration { step, paused } = self.runstate {
+            replay_input(self, ctx, step, paused);
+            if let RunState::ReplayGeneration { step, paused } = self.runstate {
+                gui::draw_generation_replay(&self.ecs, ctx, step, paused);
+                return;
+            }
+        }
+
+        // So does the level editor.
+        if self.runstate == RunState::LevelEditor {
+            if editor::input(&mut self.ecs, ctx) {
+                gui::draw_editor(&self.ecs, ctx);
+                return;
+            }
+            self.runstate = RunState::Running;
+        }
+
         // Handle player input.
-        player_input(self, ctx);
+        match self.runstate {
+            RunState::Running => match self.autotravel {
+                Some(travel) => autotravel_input(self, ctx, travel),
+                None => player_input(self, ctx),
+            },
+            RunState::Look { cursor } => look_input(self, ctx, cursor),
+            RunState::CloseDoor => close_door_input(self, ctx),
+            RunState::ShowTargeting {
+                item,
+                range,
+                cursor,
+            } => targeting_input(self, ctx, item, range, cursor),
+            RunState::WizardConsole => {
+                if !wizard::input(self, ctx) {
+                    self.runstate = RunState::Running;
+                }
+            }
+            // Menus handle their own input while being drawn.
+            RunState::ShowInventory | RunState::ShowDropItem | RunState::ShowEquipment => {}
+            // Nothing left to do...
+            RunState::Ticking
+            | RunState::GameOver
+            | RunState::ReplayGeneration { .. }
+            | RunState::LevelEditor => {}
+        }
 
         // Run game systems.
-        self.run_systems();
+        if self.runstate == RunState::Ticking {
+            self.advance_world();
+        }
+        if self.runstate != RunState::GameOver {
+            self.run_systems();
+        }
 
         // Draw map.
-        let mp = self.ecs.fetch::<Map>();
-        let viewsheds = self.ecs.read_storage::<cmp::Viewshed>();
-        let player = self.ecs.read_storage::<cmp::Player>();
-        let (pl_viewshed, _pl) = (&viewsheds, &player)
-            .join()
-            .single()
-            // TODO: parameterize err msg.
-            .unwrap_or_else(|e| panic!("Problem with findings player's viewshed: {}", e));
-        mp.draw(pl_viewshed, ctx);
+        {
+            let mp = self.ecs.fetch::<Map>();
+            let viewsheds = self.ecs.read_storage::<cmp::Viewshed>();
+            let player = self.ecs.read_storage::<cmp::Player>();
+            let (pl_viewshed, _pl) = (&viewsheds, &player)
+                .join()
+                .single()
+                // TODO: parameterize err msg.
+                .unwrap_or_else(|e| panic!("Problem with findings player's viewshed: {}", e));
+            mp.draw(pl_viewshed, ctx);
+        }
 
         // Draw entities.
-        let positions = self.ecs.read_storage::<cmp::Pos>();
-        let renderables = self.ecs.read_storage::<cmp::Renderable>();
+        {
+            let positions = self.ecs.read_storage::<cmp::Pos>();
+            let renderables = self.ecs.read_storage::<cmp::Renderable>();
+            let items = self.ecs.read_storage::<cmp::Item>();
+            let hidden = self.ecs.read_storage::<cmp::Hidden>();
 
-        for (pos, render) in (&positions, &renderables).join() {
-            ctx.set(pos.x, pos.y, render.fg, render.bg, render.glyph);
+            // Items first, so that they don't hide whoever is standing on them.
+            for (pos, render, _, _) in (&positions, &renderables, &items, !&hidden).join() {
+                ctx.set(pos.x, pos.y, render.fg, render.bg, render.glyph);
+            }
+            for (pos, render, _, _) in (&positions, &renderables, !&items, !&hidden).join() {
+                ctx.set(pos.x, pos.y, render.fg, render.bg, render.glyph);
+            }
+        }
+
+        // Draw UI panel.
+        gui::draw_ui(&self.ecs, ctx);
+
+        // Draw tooltips and menus (keyboard cursor takes precedence over mouse).
+        let player = player_entity(&self.ecs);
+        match self.runstate {
+            RunState::Running | RunState::Ticking | RunState::CloseDoor => {
+                let mouse = ctx.mouse_point();
+                gui::draw_tooltip(&self.ecs, ctx, mouse);
+            }
+            RunState::Look { cursor } => {
+                gui::draw_cursor(ctx, cursor);
+                gui::draw_tooltip(&self.ecs, ctx, cursor);
+            }
+            RunState::ShowInventory => {
+                match gui::show_inventory(&self.ecs, ctx, player, "Inventory") {
+                    MenuResult::Cancel => self.runstate = RunState::Running,
+                    MenuResult::NoResponse => {}
+                    MenuResult::Selected(item)
+                        if self.ecs.read_storage::<cmp::Equippable>().contains(item) =>
+                    {
+                        self.ecs
+                            .write_storage::<cmp::WantsToEquip>()
+                            .insert(player, cmp::WantsToEquip { item })
+                            .expect("Unable to insert want to equip");
+                        end_player_turn(self, EQUIP_COST);
+                    }
+                    MenuResult::Selected(item) => {
+                        // Ranged items need a target, the others are used on oneself.
+                        let range = self
+                            .ecs
+                            .read_storage::<cmp::Ranged>()
+                            .get(item)
+                            .map(|ranged| ranged.range);
+                        match range {
+                            Some(range) => {
+                                self.runstate = RunState::ShowTargeting {
+                                    item,
+                                    range,
+                                    cursor: player_pos(&self.ecs),
+                                }
+                            }
+                            None => {
+                                self.ecs
+                                    .write_storage::<cmp::WantsToUseItem>()
+                                    .insert(player, cmp::WantsToUseItem { item, target: None })
+                                    .expect("Unable to insert want to use item");
+                                end_player_turn(self, USE_ITEM_COST);
+                            }
+                        }
+                    }
+                }
+            }
+            RunState::ShowTargeting { range, cursor, .. } => {
+                gui::draw_targeting(&self.ecs, ctx, range, cursor);
+                gui::draw_tooltip(&self.ecs, ctx, cursor);
+            }
+            RunState::GameOver => gui::draw_game_over(ctx),
+            RunState::WizardConsole => gui::draw_console(&self.ecs, ctx),
+            RunState::ReplayGeneration { .. } | RunState::LevelEditor => {}
+            RunState::ShowEquipment => match gui::show_equipment(&self.ecs, ctx, player) {
+                MenuResult::Cancel => self.runstate = RunState::Running,
+                MenuResult::NoResponse => {}
+                MenuResult::Selected(item) => {
+                    self.ecs
+                        .write_storage::<cmp::WantsToUnequip>()
+                        .insert(player, cmp::WantsToUnequip { item })
+                        .expect("Unable to insert want to unequip");
+                    end_player_turn(self, EQUIP_COST);
+                }
+            },
+            RunState::ShowDropItem => {
+                match gui::show_inventory(&self.ecs, ctx, player, "Drop which item?") {
+                    MenuResult::Cancel => self.runstate = RunState::Running,
+                    MenuResult::NoResponse => {}
+                    MenuResult::Selected(item) => {
+                        self.ecs
+                            .write_storage::<cmp::WantsToDrop>()
+                            .insert(player, cmp::WantsToDrop { item })
+                            .expect("Unable to insert want to drop");
+                        end_player_turn(self, DROP_COST);
+                    }
+                }
+            }
         }
     }
 }
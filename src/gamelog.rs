@@ -0,0 +1,17 @@
+/// Messages describing what happened in game, newest last
+#[derive(Default)]
+pub struct GameLog {
+    pub entries: Vec<String>,
+}
+
+impl GameLog {
+    /// Add new message to the log
+    pub fn log<S: Into<String>>(&mut self, msg: S) {
+        self.entries.push(msg.into());
+    }
+
+    /// Iterate over the last `n` messages (newest first)
+    pub fn last(&self, n: usize) -> impl Iterator<Item = &String> {
+        self.entries.iter().rev().take(n)
+    }
+}
@@ -0,0 +1,381 @@
+use crate::components as cmp;
+use crate::consts::{MAP_HEIGHT, SCREEN_WIDTH, UI_HEIGHT};
+use crate::editor::Editor;
+use crate::game_state::{player_entity, player_pos};
+use crate::gamelog::GameLog;
+use crate::input::{self, Action};
+use crate::map::history::History;
+use crate::map::{Map, Tile};
+use crate::utils::rect::Rect;
+use crate::wizard::WizardConsole;
+use rltk::{Console, Point, Rltk, RGB};
+use single::Single;
+use specs::prelude::*;
+
+/// Tiles in the player's viewshed
+fn player_visible_tiles(ecs: &World) -> Vec<Point> {
+    let viewsheds = ecs.read_storage::<cmp::Viewshed>();
+    let players = ecs.read_storage::<cmp::Player>();
+    let (pl_viewshed, _pl) = (&viewsheds, &players)
+        .join()
+        .single()
+        .unwrap_or_else(|e| panic!("Problem with findings player's viewshed: {}", e));
+
+    pl_viewshed.visible_tiles.clone()
+}
+
+/// Checks if `target` is in the player's viewshed
+fn player_can_see(ecs: &World, target: Point) -> bool {
+    player_visible_tiles(ecs).contains(&target)
+}
+
+/// Draws a tooltip describing the tile at `target` and the named entities standing on it.
+///
+/// Nothing is drawn if `target` is not visible by the player.
+pub fn draw_tooltip(ecs: &World, ctx: &mut Rltk, target: Point) {
+    let map = ecs.fetch::<Map>();
+    if !map.contains_point(target.x, target.y) {
+        return;
+    }
+
+    // Only describe what the player can actually see.
+    if !player_can_see(ecs, target) {
+        return;
+    }
+
+    // Tile kind first, then whatever stands on it.
+    let mut lines = vec![map.at(target.x, target.y).name().to_string()];
+    let names = ecs.read_storage::<cmp::Name>();
+    let positions = ecs.read_storage::<cmp::Pos>();
+    let hidden = ecs.read_storage::<cmp::Hidden>();
+    for (name, pos, _) in (&names, &positions, !&hidden).join() {
+        if pos.x == target.x && pos.y == target.y {
+            lines.push(name.name.clone());
+        }
+    }
+
+    // Put the tooltip on the right of the target, unless it would go off screen.
+    let width = lines.iter().map(|l| l.len()).max().unwrap_or(0) as i32 + 2;
+    let height = lines.len() as i32 + 1;
+    let x = if target.x + width + 1 < map.width as i32 {
+        target.x + 1
+    } else {
+        target.x - width - 1
+    };
+    let y = std::cmp::min(target.y, map.height as i32 - height - 1);
+
+    let fg = RGB::named(rltk::WHITE);
+    let bg = RGB::named(rltk::GREY);
+    ctx.draw_box(x, y, width, height, fg, bg);
+    for (i, line) in lines.iter().enumerate() {
+        ctx.print_color(x + 1, y + 1 + i as i32, fg, bg, line);
+    }
+}
+
+/// Checks if the player can aim something with range `range` at `target`.
+pub fn is_targetable(ecs: &World, range: i32, target: Point) -> bool {
+    let distance = rltk::DistanceAlg::Pythagoras.distance2d(player_pos(ecs), target);
+
+    distance <= range as f32 && player_can_see(ecs, target)
+}
+
+/// Highlights the tiles which can be targeted (and the targeting cursor).
+pub fn draw_targeting(ecs: &World, ctx: &mut Rltk, range: i32, cursor: Point) {
+    let origin = player_pos(ecs);
+    for tile in player_visible_tiles(ecs) {
+        if rltk::DistanceAlg::Pythagoras.distance2d(origin, tile) <= range as f32 {
+            ctx.set_bg(tile.x, tile.y, RGB::named(rltk::BLUE));
+        }
+    }
+
+    let cursor_bg = if is_targetable(ecs, range, cursor) {
+        RGB::named(rltk::CYAN)
+    } else {
+        RGB::named(rltk::RED)
+    };
+    ctx.set_bg(cursor.x, cursor.y, cursor_bg);
+
+    ctx.print_color(
+        2,
+        0,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "Select target: SPACE or click to confirm, ESCAPE to cancel",
+    );
+}
+
+/// Tells the player that the game is over.
+pub fn draw_game_over(ctx: &mut Rltk) {
+    let fg = RGB::named(rltk::RED);
+    let bg = RGB::named(rltk::BLACK);
+    ctx.draw_box(25, 20, 30, 4, fg, bg);
+    ctx.print_color(34, 22, fg, bg, "YOU ARE DEAD");
+}
+
+/// Draws step `step` of the recorded map generation, with the replay status on the UI panel.
+pub fn draw_generation_replay(ecs: &World, ctx: &mut Rltk, step: usize, paused: bool) {
+    let history = ecs.fetch::<History>();
+    if let Some(snapshot) = history.get(step) {
+        snapshot.draw(ctx);
+    }
+
+    let fg = RGB::named(rltk::WHITE);
+    let bg = RGB::named(rltk::BLACK);
+    let top = MAP_HEIGHT as i32;
+    ctx.draw_box(
+        0,
+        top,
+        SCREEN_WIDTH as i32 - 1,
+        UI_HEIGHT as i32 - 1,
+        fg,
+        bg,
+    );
+    let status = if paused { "paused" } else { "playing" };
+    let title = format!(
+        " Generation step {} / {} ({}) ",
+        step + 1,
+        history.len(),
+        status
+    );
+    ctx.print_color(2, top, RGB::named(rltk::YELLOW), bg, &title);
+    ctx.print(2, top + 1, "Confirm: pause/resume");
+    ctx.print(2, top + 2, "West/East: step back/forward");
+    ctx.print(2, top + 3, "Cancel: back to the game");
+}
+
+/// Draws the level being edited (all of it, hidden things included), with the editor status on
+/// the UI panel.
+pub fn draw_editor(ecs: &World, ctx: &mut Rltk) {
+    let editor = ecs.fetch::<Editor>();
+    let map = ecs.fetch::<Map>();
+    let black = RGB::named(rltk::BLACK);
+    for (idx, tile) in map.tiles.iter().enumerate() {
+        let p = map.idx_xy(idx);
+        let fg = match tile {
+            Tile::Wall => RGB::named(rltk::GREY),
+            _ => RGB::named(rltk::WHITE),
+        };
+        ctx.set(p.x, p.y, fg, black, rltk::to_cp437(tile.glyph()));
+    }
+
+    let positions = ecs.read_storage::<cmp::Pos>();
+    let renderables = ecs.read_storage::<cmp::Renderable>();
+    for (pos, render) in (&positions, &renderables).join() {
+        ctx.set(pos.x, pos.y, render.fg, render.bg, render.glyph);
+    }
+
+    // Highlight the selected room, the first corner of the room being drawn and the mouse.
+    if let Some(room) = editor.selected.and_then(|id| map.rooms.get(id)) {
+        for y in room.y..room.y + room.height {
+            for x in room.x..room.x + room.width {
+                ctx.set_bg(x, y, RGB::named(rltk::BLUE));
+            }
+        }
+    }
+    if let Some(corner) = editor.corner {
+        ctx.set_bg(corner.x, corner.y, RGB::named(rltk::CYAN));
+    }
+    let mouse = ctx.mouse_point();
+    if map.contains_point(mouse.x, mouse.y) {
+        ctx.set_bg(mouse.x, mouse.y, RGB::named(rltk::MAGENTA));
+    }
+
+    let fg = RGB::named(rltk::WHITE);
+    let top = MAP_HEIGHT as i32;
+    ctx.draw_box(
+        0,
+        top,
+        SCREEN_WIDTH as i32 - 1,
+        UI_HEIGHT as i32 - 1,
+        fg,
+        black,
+    );
+    let title = match editor.brush() {
+        Some(brush) => format!(" Level editor: {:?} ({}) ", editor.tool, brush),
+        None => format!(" Level editor: {:?} ", editor.tool),
+    };
+    ctx.print_color(2, top, RGB::named(rltk::YELLOW), black, &title);
+    ctx.print(2, top + 1, editor.tool.help());
+    ctx.print(
+        2,
+        top + 2,
+        "Next tool/brush, Previous brush: choose what to do, Delete: remove",
+    );
+    ctx.print(
+        2,
+        top + 3,
+        &format!("Moves: resize room, Save: save to {}", editor.path),
+    );
+    ctx.print(2, top + 4, "Cancel/Level editor: back to the game");
+
+    // Feedback of the last edit.
+    if let Some(msg) = ecs.fetch::<GameLog>().last(1).next() {
+        ctx.print_color(2, top + 5, RGB::named(rltk::GREEN), black, msg);
+    }
+}
+
+/// Rows taken by the wizard console (frame included)
+const CONSOLE_HEIGHT: usize = 16;
+
+/// Draws the wizard console over the top of the map: the latest lines and what's being typed.
+pub fn draw_console(ecs: &World, ctx: &mut Rltk) {
+    let console = ecs.fetch::<WizardConsole>();
+    let fg = RGB::named(rltk::WHITE);
+    let bg = RGB::named(rltk::BLACK);
+    ctx.draw_box(
+        0,
+        0,
+        SCREEN_WIDTH as i32 - 1,
+        CONSOLE_HEIGHT as i32 - 1,
+        fg,
+        bg,
+    );
+    ctx.print_color(2, 0, RGB::named(rltk::YELLOW), bg, " Wizard console ");
+
+    // Newest lines at the bottom, right above the prompt.
+    let shown = CONSOLE_HEIGHT - 3;
+    let skipped = console.lines.len().saturating_sub(shown);
+    for (i, line) in console.lines.iter().skip(skipped).enumerate() {
+        ctx.print(2, 1 + i as i32, line);
+    }
+    let prompt = format!("> {}_", console.input);
+    ctx.print_color(
+        2,
+        CONSOLE_HEIGHT as i32 - 2,
+        RGB::named(rltk::GREEN),
+        bg,
+        &prompt,
+    );
+}
+
+/// Highlights the look mode cursor.
+pub fn draw_cursor(ctx: &mut Rltk, cursor: Point) {
+    ctx.set_bg(cursor.x, cursor.y, RGB::named(rltk::MAGENTA));
+}
+
+/// Draws the UI panel at the bottom of the screen (below the map).
+pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
+    let fg = RGB::named(rltk::WHITE);
+    let bg = RGB::named(rltk::BLACK);
+    let top = MAP_HEIGHT as i32;
+    ctx.draw_box(
+        0,
+        top,
+        SCREEN_WIDTH as i32 - 1,
+        UI_HEIGHT as i32 - 1,
+        fg,
+        bg,
+    );
+
+    // Player status (on the panel's frame).
+    let player = player_entity(ecs);
+    if let Some(stats) = ecs.read_storage::<cmp::CombatStats>().get(player) {
+        let hp = format!(" HP: {} / {} ", stats.hp, stats.max_hp);
+        ctx.print_color(2, top, RGB::named(rltk::YELLOW), bg, &hp);
+    }
+    if let Some(clock) = ecs.read_storage::<cmp::HungerClock>().get(player) {
+        let (text, colour) = match clock.state {
+            cmp::HungerState::WellFed => (" Well Fed ", rltk::GREEN),
+            cmp::HungerState::Normal => ("", rltk::WHITE),
+            cmp::HungerState::Hungry => (" Hungry ", rltk::ORANGE),
+            cmp::HungerState::Starving => (" Starving ", rltk::RED),
+        };
+        ctx.print_color(SCREEN_WIDTH as i32 - 14, top, RGB::named(colour), bg, text);
+    }
+
+    // Most recent log messages.
+    let log = ecs.fetch::<GameLog>();
+    for (i, msg) in log.last(UI_HEIGHT - 2).enumerate() {
+        ctx.print(2, top + 1 + i as i32, msg);
+    }
+}
+
+/// Outcome of a menu
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum MenuResult<T> {
+    /// User closed the menu
+    Cancel,
+    /// User is still making up their mind
+    NoResponse,
+    /// User chose something
+    Selected(T),
+}
+
+/// Shows the items in the backpack of `owner` and lets the user choose one of them.
+pub fn show_inventory(
+    ecs: &World,
+    ctx: &mut Rltk,
+    owner: Entity,
+    title: &str,
+) -> MenuResult<Entity> {
+    let entities = ecs.entities();
+    let names = ecs.read_storage::<cmp::Name>();
+    let backpacks = ecs.read_storage::<cmp::InBackpack>();
+    let items: Vec<(Entity, String)> = (&entities, &names, &backpacks)
+        .join()
+        .filter(|(_, _, backpack)| backpack.owner == owner)
+        .map(|(item, name, _)| (item, name.name.clone()))
+        .collect();
+
+    show_item_menu(ecs, ctx, title, &items)
+}
+
+/// Shows the items equipped by `owner` and lets the user choose one of them.
+pub fn show_equipment(ecs: &World, ctx: &mut Rltk, owner: Entity) -> MenuResult<Entity> {
+    let entities = ecs.entities();
+    let names = ecs.read_storage::<cmp::Name>();
+    let equipped = ecs.read_storage::<cmp::Equipped>();
+    let items: Vec<(Entity, String)> = (&entities, &names, &equipped)
+        .join()
+        .filter(|(_, _, eq)| eq.owner == owner)
+        .map(|(item, name, eq)| (item, format!("{:?}: {}", eq.slot, name.name)))
+        .collect();
+
+    show_item_menu(ecs, ctx, "Unequip which item?", &items)
+}
+
+/// Shows a list of (item, description) pairs and lets the user choose one item.
+fn show_item_menu(
+    ecs: &World,
+    ctx: &mut Rltk,
+    title: &str,
+    items: &[(Entity, String)],
+) -> MenuResult<Entity> {
+    // Frame.
+    let fg = RGB::named(rltk::WHITE);
+    let bg = RGB::named(rltk::BLACK);
+    let highlight = RGB::named(rltk::YELLOW);
+    let (x, y, width) = (15, 10, 31);
+    let height = items.len() as i32 + 3;
+    ctx.draw_box(x, y, width, height, fg, bg);
+    ctx.print_color(x + 3, y, highlight, bg, title);
+    ctx.print_color(x + 3, y + height, highlight, bg, "ESCAPE to cancel");
+
+    // One line per item, each selectable by a letter.
+    if items.is_empty() {
+        ctx.print(x + 2, y + 2, "(empty)");
+    }
+    for (i, (_, name)) in items.iter().enumerate() {
+        let line_y = y + 2 + i as i32;
+        ctx.set(x + 2, line_y, fg, bg, rltk::to_cp437('('));
+        ctx.set(x + 3, line_y, highlight, bg, 97 + i as u8);
+        ctx.set(x + 4, line_y, fg, bg, rltk::to_cp437(')'));
+        ctx.print(x + 6, line_y, name);
+    }
+
+    // Handle choice.
+    if input::pressed_action(ecs, ctx) == Some(Action::Cancel) {
+        return MenuResult::Cancel;
+    }
+    match ctx.key {
+        None => MenuResult::NoResponse,
+        Some(key) => {
+            let choice = rltk::letter_to_option(key);
+            if choice >= 0 && (choice as usize) < items.len() {
+                MenuResult::Selected(items[choice as usize].0)
+            } else {
+                MenuResult::NoResponse
+            }
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use crate::utils::dir::Dir;
+use rltk::{Rltk, VirtualKeyCode};
+use serde::Deserialize;
+use specs::prelude::*;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// Default key bindings (also shipped as a config file).
+const DEFAULT_KEYMAP: &str = include_str!("../config/keymap.yaml");
+
+/// Something the player wants to do, independently from the key used to do it
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    MoveNorth,
+    MoveSouth,
+    MoveWest,
+    MoveEast,
+    MoveNorthWest,
+    MoveNorthEast,
+    MoveSouthWest,
+    MoveSouthEast,
+    Wait,
+    PickUp,
+    Look,
+    AutoExplore,
+    CloseDoor,
+    Descend,
+    Inventory,
+    Drop,
+    Equipment,
+    ReplayGeneration,
+    LevelEditor,
+    WizardConsole,
+    NextTool,
+    NextBrush,
+    PreviousBrush,
+    Save,
+    Delete,
+    Confirm,
+    Cancel,
+}
+
+impl Action {
+    /// Movement direction associated to action (if it is a movement action)
+    pub fn move_dir(self) -> Option<Dir> {
+        use Action::*;
+        match self {
+            MoveNorth => Some(Dir::North),
+            MoveSouth => Some(Dir::South),
+            MoveWest => Some(Dir::West),
+            MoveEast => Some(Dir::East),
+            MoveNorthWest => Some(Dir::NorthWest),
+            MoveNorthEast => Some(Dir::NorthEast),
+            MoveSouthWest => Some(Dir::SouthWest),
+            MoveSouthEast => Some(Dir::SouthEast),
+            _ => None,
+        }
+    }
+}
+
+/// Maps keys to actions
+pub struct Keymap {
+    bindings: HashMap<VirtualKeyCode, Action>,
+}
+
+impl Keymap {
+    /// Parse keymap from its YAML representation (action -> list of key names)
+    pub fn parse(src: &str) -> Result<Self, Box<dyn Error>> {
+        let raw: HashMap<Action, Vec<String>> = serde_yaml::from_str(src)?;
+
+        let mut bindings = HashMap::new();
+        for (action, keys) in raw {
+            for key_name in keys {
+                let key = key_from_name(&key_name)
+                    .ok_or_else(|| format!("Unknown key name in keymap: {}", key_name))?;
+                if let Some(old) = bindings.insert(key, action) {
+                    return Err(format!(
+                        "Key {} bound to both {:?} and {:?}",
+                        key_name, old, action
+                    )
+                    .into());
+                }
+            }
+        }
+
+        Ok(Keymap { bindings })
+    }
+
+    /// Load keymap from config file
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Action bound to given key (if any)
+    pub fn action(&self, key: VirtualKeyCode) -> Option<Action> {
+        self.bindings.get(&key).copied()
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::parse(DEFAULT_KEYMAP).expect("Default keymap is broken")
+    }
+}
+
+/// Action the user wants to perform this tick (according to the keymap in `ecs`)
+pub fn pressed_action(ecs: &World, ctx: &Rltk) -> Option<Action> {
+    ctx.key.and_then(|key| ecs.fetch::<Keymap>().action(key))
+}
+
+/// Go from the name of a key (as written in config files) to the key itself
+fn key_from_name(name: &str) -> Option<VirtualKeyCode> {
+    use VirtualKeyCode::*;
+    Some(match name {
+        // Letters.
+        "A" => A,
+        "B" => B,
+        "C" => C,
+        "D" => D,
+        "E" => E,
+        "F" => F,
+        "G" => G,
+        "H" => H,
+        "I" => I,
+        "J" => J,
+        "K" => K,
+        "L" => L,
+        "M" => M,
+        "N" => N,
+        "O" => O,
+        "P" => P,
+        "Q" => Q,
+        "R" => R,
+        "S" => S,
+        "T" => T,
+        "U" => U,
+        "V" => V,
+        "W" => W,
+        "X" => X,
+        "Y" => Y,
+        "Z" => Z,
+        // Number row.
+        "Key0" => Key0,
+        "Key1" => Key1,
+        "Key2" => Key2,
+        "Key3" => Key3,
+        "Key4" => Key4,
+        "Key5" => Key5,
+        "Key6" => Key6,
+        "Key7" => Key7,
+        "Key8" => Key8,
+        "Key9" => Key9,
+        // Numpad.
+        "Numpad0" => Numpad0,
+        "Numpad1" => Numpad1,
+        "Numpad2" => Numpad2,
+        "Numpad3" => Numpad3,
+        "Numpad4" => Numpad4,
+        "Numpad5" => Numpad5,
+        "Numpad6" => Numpad6,
+        "Numpad7" => Numpad7,
+        "Numpad8" => Numpad8,
+        "Numpad9" => Numpad9,
+        // Function keys.
+        "F1" => F1,
+        "F2" => F2,
+        "F3" => F3,
+        "F4" => F4,
+        "F5" => F5,
+        "F6" => F6,
+        "F7" => F7,
+        "F8" => F8,
+        "F9" => F9,
+        "F10" => F10,
+        "F11" => F11,
+        "F12" => F12,
+        // Arrows.
+        "Left" => Left,
+        "Right" => Right,
+        "Up" => Up,
+        "Down" => Down,
+        // Misc.
+        "Escape" => Escape,
+        "Return" => Return,
+        "Space" => Space,
+        "Tab" => Tab,
+        "Back" => Back,
+        "Delete" => Delete,
+        "Period" => Period,
+        "Comma" => Comma,
+        "Slash" => Slash,
+        "Grave" => Grave,
+        "Home" => Home,
+        "End" => End,
+        "PageUp" => PageUp,
+        "PageDown" => PageDown,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_keymap_has_vi_keys_and_numpad() {
+        let keymap = Keymap::default();
+
+        assert_eq!(keymap.action(VirtualKeyCode::H), Some(Action::MoveWest));
+        assert_eq!(
+            keymap.action(VirtualKeyCode::Y),
+            Some(Action::MoveNorthWest)
+        );
+        assert_eq!(
+            keymap.action(VirtualKeyCode::Numpad3),
+            Some(Action::MoveSouthEast)
+        );
+        assert_eq!(keymap.action(VirtualKeyCode::Up), Some(Action::MoveNorth));
+    }
+
+    #[test]
+    fn parse_rejects_unknown_keys() {
+        assert!(Keymap::parse("wait: [NotAKey]").is_err());
+    }
+
+    #[test]
+    fn parse_rejects_double_bindings() {
+        assert!(Keymap::parse("wait: [A]\nlook: [A]").is_err());
+    }
+}
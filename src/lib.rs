@@ -0,0 +1,7 @@
+//! Game independent parts of the roguelike (map generation and friends), shared by the game and
+//! the tools in `src/bin`.
+
+pub mod components;
+pub mod consts;
+pub mod map;
+pub mod utils;
@@ -1,20 +1,82 @@
-mod components;
-mod consts;
+mod editor;
+mod effects;
+mod gamelog;
 mod game_state;
-mod map;
+mod gui;
+mod input;
+mod raws;
+mod spawner;
 mod systems;
-mod utils;
+mod wizard;
+
+// Shared with the tools.
+use tutrl::{components, consts, map, utils};
 
 use crate::components as cmp;
-use crate::game_state::State;
-use consts::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::game_state::{RunState, State};
+use editor::Editor;
+use effects::EffectQueue;
+use gamelog::GameLog;
+use input::Keymap;
+use log::warn;
 use log4rs;
 use map::gen::rnc;
-use rand::seq::SliceRandom;
-use rltk::RGB;
+use map::history::History;
+use map::level::Level;
+use map::movement::MovementRules;
+use map::pathfinding::Pathfinder;
+use map::Map;
+use raws::Raws;
 use specs::prelude::*;
+use systems::initiative::Clock;
+use utils::args::Args;
+use wizard::WizardConsole;
+
+const USAGE: &str = "\
+Usage: tutrl [OPTIONS]
+
+Options:
+        --level PATH  play the hand-made level saved at PATH (where the editor saves too)
+        --map PATH    play on the map saved at PATH
+        --record-gen  record the generation of the map, so that it can be replayed
+    -h, --help        print this message";
+
+/// Command line options
+struct Options {
+    level: Option<String>,
+    map: Option<String>,
+    record_gen: bool,
+}
+
+impl Options {
+    /// Parse options from command line arguments (program name excluded)
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut opts = Options {
+            level: None,
+            map: None,
+            record_gen: false,
+        };
+
+        let mut args = Args::new(args, USAGE);
+        while let Some(option) = args.next_option() {
+            match option.as_str() {
+                "--level" => opts.level = Some(args.value()?),
+                "--map" => opts.map = Some(args.value()?),
+                "--record-gen" => opts.record_gen = true,
+                _ => return Err(args.unknown()),
+            }
+        }
+
+        Ok(opts)
+    }
+}
 
 fn main() {
+    let opts = Options::parse(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
     // Setup logger backend
     log4rs::init_file("config/log4rs.yaml", Default::default()).unwrap();
 
@@ -25,7 +87,11 @@ fn main() {
         .build();
 
     // Set up initial game state
-    let mut gs = State { ecs: World::new() };
+    let mut gs = State {
+        ecs: World::new(),
+        runstate: RunState::Ticking,
+        autotravel: None,
+    };
 
     // Add components
     // TODO: use init
@@ -33,61 +99,145 @@ fn main() {
     gs.ecs.register::<cmp::Renderable>();
     gs.ecs.register::<cmp::Player>();
     gs.ecs.register::<cmp::Viewshed>();
+    gs.ecs.register::<cmp::LightSource>();
+    gs.ecs.register::<cmp::Name>();
+    gs.ecs.register::<cmp::Item>();
+    gs.ecs.register::<cmp::InBackpack>();
+    gs.ecs.register::<cmp::WantsToPickup>();
+    gs.ecs.register::<cmp::WantsToDrop>();
+    gs.ecs.register::<cmp::CombatStats>();
+    gs.ecs.register::<cmp::WantsToUseItem>();
+    gs.ecs.register::<cmp::Consumable>();
+    gs.ecs.register::<cmp::Ranged>();
+    gs.ecs.register::<cmp::AreaOfEffect>();
+    gs.ecs.register::<cmp::ProvidesHealing>();
+    gs.ecs.register::<cmp::InflictsDamage>();
+    gs.ecs.register::<cmp::Confusion>();
+    gs.ecs.register::<cmp::Teleports>();
+    gs.ecs.register::<cmp::Blinds>();
+    gs.ecs.register::<cmp::InflictsPoison>();
+    gs.ecs.register::<cmp::ProvidesRegeneration>();
+    gs.ecs.register::<cmp::ProvidesFood>();
+    gs.ecs.register::<cmp::Confused>();
+    gs.ecs.register::<cmp::Blind>();
+    gs.ecs.register::<cmp::Poisoned>();
+    gs.ecs.register::<cmp::Regenerating>();
+    gs.ecs.register::<cmp::HungerClock>();
+    gs.ecs.register::<cmp::Equippable>();
+    gs.ecs.register::<cmp::Equipped>();
+    gs.ecs.register::<cmp::MeleePowerBonus>();
+    gs.ecs.register::<cmp::DefenseBonus>();
+    gs.ecs.register::<cmp::WantsToEquip>();
+    gs.ecs.register::<cmp::WantsToUnequip>();
+    gs.ecs.register::<cmp::WantsToMelee>();
+    gs.ecs.register::<cmp::Monster>();
+    gs.ecs.register::<cmp::Dialogue>();
+    gs.ecs.register::<cmp::Energy>();
+    gs.ecs.register::<cmp::MyTurn>();
+    gs.ecs.register::<cmp::Key>();
+    gs.ecs.register::<cmp::Hidden>();
+    gs.ecs.register::<cmp::EntryTrigger>();
+    gs.ecs.register::<cmp::SingleActivation>();
+    gs.ecs.register::<cmp::EntityMoved>();
+    gs.ecs.register::<cmp::Invulnerable>();
+    gs.ecs.register::<cmp::Omniscient>();
 
-    // Inizialie map
-    let map =
-        // Generate game map (only one for now)
-        rnc::make_map(
-            rnc::ConfigBuilder::default()
-            .map_width(SCREEN_WIDTH)
-            .map_height(SCREEN_HEIGHT)
-            .build()
-            .unwrap(),
-        )
-        .unwrap_or_else(|partial_map| {
-            // TODO: use logging
-            println!("Map generation was stopped prematurely...");
-
-            partial_map
-        });
-
-    // TODO: TEST: Create player
-    let player_spawn_point = map.idx_xy(
-        *map.tiles
-            .iter()
-            .enumerate()
-            .filter(|(_, t)| t.is_passable())
-            .map(|(i, _)| i)
-            .collect::<Vec<_>>()
-            .choose(&mut rand::thread_rng())
-            .expect("Could not pick player spawn point"),
-    );
-    gs.ecs
-        .create_entity()
-        .with(cmp::Player)
-        .with(cmp::Pos::from(player_spawn_point))
-        .with(cmp::Viewshed::new(10))
-        .with(cmp::Renderable {
-            glyph: rltk::to_cp437('@'),
-            fg: RGB::named(rltk::WHITE),
-            bg: RGB::named(rltk::BLACK),
-        })
-        .build();
+    // Inizialie map: a hand-made level (`--level PATH`), a fixed map (`--map PATH`) or a generated
+    // one (recording its generation with `--record-gen`, so that it can be replayed)
+    let Options {
+        level: level_path,
+        map: fixed_map,
+        record_gen,
+    } = opts;
+    let mut history = if record_gen {
+        History::recording()
+    } else {
+        History::default()
+    };
+    let level = level_path
+        .as_ref()
+        .map(|path| Level::load(path).expect("Unable to load level"));
+    let (mut map, level) = match level {
+        Some(Level {
+            map,
+            start,
+            entities,
+        }) => (map, Some((start, entities))),
+        // Load a fixed map (e.g. a hand-fixed generated one)
+        None => match &fixed_map {
+            Some(path) => (Map::load(path).expect("Unable to load fixed map"), None),
+            // Generate game map (only one for now)
+            None => (
+                rnc::make_map_observed(rnc::Config::game(), &mut history).unwrap_or_else(
+                    |partial_map| {
+                        // TODO: use logging
+                        println!("Map generation was stopped prematurely...");
+
+                        partial_map
+                    },
+                ),
+                None,
+            ),
+        },
+    };
 
-    // TODO: TEST: Create some entities
-    for i in 0..10 {
-        gs.ecs
-            .create_entity()
-            .with(cmp::Pos { x: i * 7, y: 20 })
-            .with(cmp::Viewshed::new(8))
-            .with(cmp::Renderable {
-                glyph: rltk::to_cp437('@'),
-                fg: RGB::named(rltk::RED),
-                bg: RGB::named(rltk::BLACK),
-            })
-            .build();
+    // Load movement rules (falling back to the default ones).
+    map.movement = MovementRules::load("config/movement.yaml").unwrap_or_else(|e| {
+        warn!("Could not load movement rules, using defaults: {}", e);
+        MovementRules::default()
+    });
+
+    // Load entity templates.
+    gs.ecs.insert(Raws::load("assets/raws/spawns.json").expect("Could not load raws"));
+
+    // Pick special rooms and give some a theme (fixed maps and levels come with theirs), so that
+    // replays end on the themed map.
+    let mut rng = rand::thread_rng();
+    if fixed_map.is_none() && level.is_none() {
+        map::theming::apply(&mut map, &mut rng);
+        history.record(&map, None);
     }
 
+    // Create player and populate the first level (hand-made levels come with what's on them).
+    match level {
+        Some((start, entities)) => {
+            let start = start
+                .map(|(x, y)| rltk::Point::new(x, y))
+                .unwrap_or_else(|| spawner::pick_start(&map, &mut rng));
+            spawner::spawn_player(&mut gs.ecs, start);
+            spawner::spawn_placements(&mut gs.ecs, &entities);
+        }
+        None => {
+            let player_spawn_point = spawner::pick_start(&map, &mut rng);
+            spawner::spawn_player(&mut gs.ecs, player_spawn_point);
+            spawner::populate(&mut gs.ecs, &mut map, 1, player_spawn_point, &mut rng);
+        }
+    }
+
+    // Edited levels are saved where they were loaded from.
+    let editor = Editor::new(
+        &gs.ecs.fetch::<Raws>(),
+        level_path
+            .as_ref()
+            .map_or(editor::DEFAULT_LEVEL_PATH, String::as_str),
+    );
+    gs.ecs.insert(editor);
+
+    // Start with an empty message log, no pending effects and a fresh clock
+    gs.ecs.insert(GameLog::default());
+    gs.ecs.insert(EffectQueue::default());
+    gs.ecs.insert(Clock::default());
+    gs.ecs.insert(Pathfinder::default());
+    gs.ecs.insert(history);
+    gs.ecs.insert(WizardConsole::default());
+
+    // Load key bindings (falling back to the default ones).
+    let keymap = Keymap::load("config/keymap.yaml").unwrap_or_else(|e| {
+        warn!("Could not load keymap, using defaults: {}", e);
+        Keymap::default()
+    });
+    gs.ecs.insert(keymap);
+
     // Move map into world.
     gs.ecs.insert(map);
 
@@ -1,23 +1,94 @@
 use crate::components as cmp;
+use crate::map::events::RejectReason;
+use crate::map::movement::MovementRules;
+use crate::map::regions::{Region, RoomTag};
+use crate::map::theming::Theme;
+use crate::utils::dir::Dir;
 use crate::utils::rect::Rect;
 use num::ToPrimitive;
+use rand::seq::IteratorRandom;
+use rand::Rng;
 use rltk::{Console, Point, Rltk, RGB};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
 
-#[derive(PartialEq, Copy, Clone, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Copy, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
 /// A map tile
 pub enum Tile {
     Wall,
     Floor,
+    ClosedDoor,
+    OpenDoor,
+    /// Closed door that needs a key to be opened
+    LockedDoor,
+    /// Lines the walls of libraries
+    Bookshelf,
+    /// Slow to wade through
+    ShallowWater,
+    /// Slightly slow to walk through
+    TallGrass,
 }
 
 impl Tile {
     pub fn is_passable(self) -> bool {
-        self == Self::Floor
+        match self {
+            Self::Floor | Self::OpenDoor | Self::ShallowWater | Self::TallGrass => true,
+            Self::Wall | Self::ClosedDoor | Self::LockedDoor | Self::Bookshelf => false,
+        }
+    }
+
+    /// Checks if the tile blocks sight (and light)
+    pub fn is_opaque(self) -> bool {
+        match self {
+            Self::Wall | Self::ClosedDoor | Self::LockedDoor | Self::Bookshelf => true,
+            Self::Floor | Self::OpenDoor | Self::ShallowWater | Self::TallGrass => false,
+        }
+    }
+
+    /// Default cost multiplier for walking onto the tile (see `MovementRules`)
+    ///
+    /// Closed doors can be walked onto by opening them first (which takes a bit longer).
+    pub fn move_cost(self) -> f32 {
+        match self {
+            Tile::Floor | Tile::OpenDoor => 1.,
+            Tile::TallGrass => 1.5,
+            Tile::ClosedDoor | Tile::ShallowWater => 2.,
+            Tile::Wall | Tile::LockedDoor | Tile::Bookshelf => f32::INFINITY,
+        }
+    }
+
+    /// Human readable tile name
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Wall => "Wall",
+            Self::Floor => "Floor",
+            Self::ClosedDoor => "Closed door",
+            Self::OpenDoor => "Open door",
+            Self::LockedDoor => "Locked door",
+            Self::Bookshelf => "Bookshelf",
+            Self::ShallowWater => "Shallow water",
+            Self::TallGrass => "Tall grass",
+        }
+    }
+
+    /// Character the tile is drawn with
+    pub fn glyph(self) -> char {
+        match self {
+            Self::Floor => '.',
+            Self::Wall => '#',
+            Self::ClosedDoor | Self::LockedDoor => '+',
+            Self::OpenDoor => '\'',
+            Self::Bookshelf => '&',
+            Self::ShallowWater => '~',
+            Self::TallGrass => '"',
+        }
     }
 }
 
 /// A map room
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
 pub struct Room {
     pub x: i32,
     pub y: i32,
@@ -38,11 +109,30 @@ impl Room {
 }
 
 /// The map
+#[derive(Clone)]
 pub struct Map {
     pub width: usize,
     pub height: usize,
     pub tiles: Vec<Tile>,
     pub rooms: Vec<Room>,
+    /// Tiles that are lit even without light sources (i.e. not in a dark region)
+    pub lit: Vec<bool>,
+    /// Light cast on each tile by light sources (recomputed every frame)
+    pub light: Vec<RGB>,
+    /// How entities are allowed to walk around
+    pub movement: MovementRules,
+    /// Region of each tile (see `Map::compute_regions`)
+    pub regions: Vec<Option<Region>>,
+    /// Rooms directly reachable from each room
+    pub room_links: Vec<Vec<usize>>,
+    /// What rooms are meant for
+    pub room_tags: HashMap<usize, HashSet<RoomTag>>,
+    /// Themes of the rooms that have one
+    pub room_themes: HashMap<usize, Theme>,
+    /// Tiles the player has already seen
+    pub revealed: Vec<bool>,
+    /// Bumped whenever tiles get modified (used to invalidate cached paths)
+    pub revision: u64,
 }
 
 impl Map {
@@ -53,6 +143,15 @@ impl Map {
             height,
             tiles: vec![tile; width * height],
             rooms: vec![],
+            lit: vec![true; width * height],
+            light: vec![RGB::named(rltk::BLACK); width * height],
+            movement: MovementRules::default(),
+            regions: vec![None; width * height],
+            room_links: vec![],
+            room_tags: HashMap::new(),
+            room_themes: HashMap::new(),
+            revealed: vec![false; width * height],
+            revision: 0,
         }
     }
 
@@ -117,72 +216,277 @@ impl Map {
         }
 
         let idx = self.xy_idx(x, y);
+        self.revision += 1;
         &mut self.tiles[idx]
     }
 
+    /// Checks if the tile at `idx` is lit (ambient light or some light source)
+    pub fn is_lit(&self, idx: usize) -> bool {
+        let light = self.light[idx];
+        self.lit[idx] || light.r > 0. || light.g > 0. || light.b > 0.
+    }
+
+    /// Colour of the tile at `idx`: ambient light tinted by light sources
+    fn tile_colour(&self, idx: usize) -> RGB {
+        let light = self.light[idx];
+        let ambient = if self.lit[idx] { 0.6 } else { 0. };
+        let dark = if self.is_lit(idx) { 0. } else { 0.3 };
+        RGB::from_f32(
+            f32::min(1., ambient + dark + light.r),
+            f32::min(1., ambient + dark + light.g),
+            f32::min(1., ambient + dark + light.b),
+        )
+    }
+
+    /// Pick a random passable tile (if there is any)
+    pub fn random_passable_tile<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<Point> {
+        self.random_free_tile(&HashSet::new(), rng)
+    }
+
+    /// Pick a random passable tile that isn't `blocked` (if there is any)
+    pub fn random_free_tile<R: Rng + ?Sized>(
+        &self,
+        blocked: &HashSet<usize>,
+        rng: &mut R,
+    ) -> Option<Point> {
+        self.tiles
+            .iter()
+            .enumerate()
+            .filter(|&(i, t)| t.is_passable() && !blocked.contains(&i))
+            .map(|(i, _)| i)
+            .choose(rng)
+            .map(|i| self.idx_xy(i))
+    }
+
     /// Tries to add rectangular room to map. Returns success as boolean.
     pub fn add_room(&mut self, new_room: Room) -> bool {
+        self.try_add_room(new_room).is_ok()
+    }
+
+    /// Tries to add rectangular room to map, telling why it couldn't on failure.
+    pub fn try_add_room(&mut self, new_room: Room) -> Result<(), RejectReason> {
+        self.check_room(&new_room, None)?;
+
+        // Carve room into map.
+        self.fill_room(&new_room, Tile::Floor);
+
+        // Keep track of room structure.
+        self.rooms.push(new_room);
+
+        // All is fine.
+        Ok(())
+    }
+
+    /// Checks if `new_room` would fit in the map (room `ignored`, if any, doesn't count)
+    fn check_room(&self, new_room: &Room, ignored: Option<usize>) -> Result<(), RejectReason> {
         // Cannot add room if it does not respect map bounds.
         // TODO: make this generic.
         if !self
             .trim_outer_frame(1)
             .expect("Map too small to be trimmed...")
-            .contains_rect(&new_room)
+            .contains_rect(new_room)
         {
-            return false;
+            return Err(RejectReason::OutOfBounds);
         }
 
         // Cannot add room if it intersects with already existing rooms.
-        for room in &self.rooms {
-            if room.intersect(&new_room) {
-                return false;
+        for (id, room) in self.rooms.iter().enumerate() {
+            if Some(id) != ignored && room.intersect(new_room) {
+                return Err(RejectReason::Overlap);
             }
         }
 
-        // Carve room into map.
-        for y in 0..new_room.height {
-            for x in 0..new_room.width {
-                *self.at_mut(new_room.x + x, new_room.y + y) = Tile::Floor;
+        Ok(())
+    }
+
+    /// Sets all the tiles of `room` to `tile`
+    fn fill_room(&mut self, room: &Room, tile: Tile) {
+        for y in room.y..room.y + room.height {
+            for x in room.x..room.x + room.width {
+                *self.at_mut(x, y) = tile;
             }
         }
+    }
 
-        // Keep track of room structure.
-        self.rooms.push(new_room);
+    /// Moves (or resizes) room `id` to `new_room`, unless it doesn't fit. Only the tiles the room
+    /// no longer covers get walled up (see `vacate`), and only the walls of the new room get
+    /// carved (so themed tiles are kept). The new room is lit if the old one was.
+    pub fn resize_room(&mut self, id: usize, new_room: Room) -> Result<(), RejectReason> {
+        self.check_room(&new_room, Some(id))?;
 
-        // All is fine.
-        true
+        let old_room = self.rooms[id].clone();
+        let lit = self.lit[self.xy_idx(old_room.x, old_room.y)];
+        self.vacate(&old_room, Some(&new_room));
+        for y in new_room.y..new_room.y + new_room.height {
+            for x in new_room.x..new_room.x + new_room.width {
+                if self.at(x, y) == Tile::Wall {
+                    *self.at_mut(x, y) = Tile::Floor;
+                }
+            }
+        }
+        self.rooms[id] = new_room;
+        if lit {
+            self.light_room(id);
+        }
+
+        Ok(())
+    }
+
+    /// Walls up room `id` (see `vacate`) and forgets about it (the ids of the following rooms
+    /// shift down by one, tags and themes follow them)
+    pub fn remove_room(&mut self, id: usize) {
+        let room = self.rooms.remove(id);
+        self.vacate(&room, None);
+
+        let shift = |room: usize| if room > id { room - 1 } else { room };
+        self.room_tags = std::mem::take(&mut self.room_tags)
+            .into_iter()
+            .filter(|&(room, _)| room != id)
+            .map(|(room, tags)| (shift(room), tags))
+            .collect();
+        self.room_themes = std::mem::take(&mut self.room_themes)
+            .into_iter()
+            .filter(|&(room, _)| room != id)
+            .map(|(room, theme)| (shift(room), theme))
+            .collect();
+    }
+
+    /// Walls up the tiles of `old` that `new` doesn't cover, except for doors and for the
+    /// shortest walkable paths joining the corridors that led into `old` to `new` (or to each
+    /// other if they don't touch), so that nothing gets cut off
+    fn vacate(&mut self, old: &Room, new: Option<&Room>) {
+        let in_new = |x: i32, y: i32| new.map_or(false, |room| room.contains_point(x, y));
+        let vacated = |x: i32, y: i32| old.contains_point(x, y) && !in_new(x, y);
+        let walkable = |tile: Tile| tile != Tile::Wall && tile != Tile::Bookshelf;
+        let neighbours = |x: i32, y: i32| {
+            Dir::cardinals().map(move |dir| {
+                let (dx, dy) = dir.delta();
+                (x + dx, y + dy)
+            })
+        };
+
+        // Walkable vacated tiles next to a way in (from outside both rooms) or to the new room.
+        let mut entrances = vec![];
+        let mut sources = vec![];
+        for y in old.y..old.y + old.height {
+            for x in old.x..old.x + old.width {
+                if !vacated(x, y) || !walkable(self.at(x, y)) {
+                    continue;
+                }
+                for (nx, ny) in neighbours(x, y) {
+                    if in_new(nx, ny) {
+                        sources.push((x, y));
+                    } else if !old.contains_point(nx, ny)
+                        && self.contains_point(nx, ny)
+                        && walkable(self.at(nx, ny))
+                    {
+                        entrances.push((x, y));
+                    }
+                }
+            }
+        }
+        if sources.is_empty() {
+            sources.extend(entrances.first().copied());
+        }
+
+        // Keep the paths leading from the sources to every entrance.
+        let mut came_from: HashMap<(i32, i32), Option<(i32, i32)>> =
+            sources.iter().map(|&p| (p, None)).collect();
+        let mut queue: VecDeque<(i32, i32)> = sources.into_iter().collect();
+        while let Some((x, y)) = queue.pop_front() {
+            for next in neighbours(x, y) {
+                if vacated(next.0, next.1)
+                    && walkable(self.at(next.0, next.1))
+                    && !came_from.contains_key(&next)
+                {
+                    came_from.insert(next, Some((x, y)));
+                    queue.push_back(next);
+                }
+            }
+        }
+        let mut kept = HashSet::new();
+        for entrance in entrances {
+            let mut step = Some(entrance).filter(|p| came_from.contains_key(p));
+            while let Some(p) = step {
+                if !kept.insert(p) {
+                    break;
+                }
+                step = came_from[&p];
+            }
+        }
+
+        for y in old.y..old.y + old.height {
+            for x in old.x..old.x + old.width {
+                let is_door = matches!(
+                    self.at(x, y),
+                    Tile::ClosedDoor | Tile::OpenDoor | Tile::LockedDoor
+                );
+                if vacated(x, y) && !is_door && !kept.contains(&(x, y)) {
+                    *self.at_mut(x, y) = Tile::Wall;
+                }
+            }
+        }
+    }
+
+    /// Lights up the whole of room `id`
+    pub fn light_room(&mut self, id: usize) {
+        let (x0, y0, w, h) = {
+            let room = &self.rooms[id];
+            (room.x, room.y, room.width, room.height)
+        };
+        for y in y0..y0 + h {
+            for x in x0..x0 + w {
+                let idx = self.xy_idx(x, y);
+                self.lit[idx] = true;
+            }
+        }
     }
 
     /// Draw the map.
     pub fn draw(&self, pl_viewshed: &cmp::Viewshed, ctx: &mut Rltk) {
         for y in 0..self.height {
             for x in 0..self.width {
-                let tile = self.at(x, y);
+                let glyph = rltk::to_cp437(self.at(x, y).glyph());
 
-                let glyph = match tile {
-                    Tile::Floor => rltk::to_cp437('.'),
-                    Tile::Wall => rltk::to_cp437('#'),
+                // Draw tile only if it is visible by the player (or remembered, but dimmed).
+                let idx = self.xy_idx(x, y);
+                let fg = if pl_viewshed.visible_tiles.contains(&Point::new(x, y)) {
+                    self.tile_colour(idx)
+                } else if self.revealed[idx] {
+                    RGB::named(rltk::GREY)
+                } else {
+                    continue;
                 };
-
-                // Draw tile only if it is visible by the player.
-                if pl_viewshed.visible_tiles.contains(&Point::new(x, y)) {
-                    ctx.set(
-                        x as i32,
-                        y as i32,
-                        RGB::named(rltk::WHITE),
-                        RGB::named(rltk::BLACK),
-                        glyph,
-                    );
-                }
+                ctx.set(x as i32, y as i32, fg, RGB::named(rltk::BLACK), glyph);
             }
         }
     }
 }
 
+/// Plain text rendering of the map (one line per row, tiles drawn with their glyphs)
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in self.tiles.chunks(self.width) {
+            let line: String = row.iter().map(|t| t.glyph()).collect();
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn maps_are_displayed_as_glyphs() {
+        let mut map = Map::all(3, 2, Tile::Wall);
+        *map.at_mut(1, 0) = Tile::Floor;
+        *map.at_mut(2, 1) = Tile::ClosedDoor;
+
+        assert_eq!(map.to_string(), "#.#\n##+\n");
+    }
+
     #[test]
     fn idx_xy_simple() {
         // The test map.
@@ -200,6 +504,92 @@ mod tests {
         assert_eq!(map.idx_xy(3), Point::new(3, 0));
     }
 
+    #[test]
+    fn dark_tiles_are_lit_by_light_sources() {
+        let mut map = Map::empty(3, 3);
+        assert!(map.is_lit(4));
+
+        map.lit[4] = false;
+        assert!(!map.is_lit(4));
+
+        map.light[4] = RGB::from_f32(0.5, 0.3, 0.);
+        assert!(map.is_lit(4));
+    }
+
+    #[test]
+    fn rooms_can_be_resized_and_removed() {
+        let mut map = Map::all(12, 6, Tile::Wall);
+        let room = |x, y, width| Room {
+            x,
+            y,
+            width,
+            height: 3,
+        };
+        map.add_room(room(1, 1, 3));
+        map.add_room(room(6, 2, 3));
+        map.room_themes.insert(1, Theme::Library);
+
+        assert_eq!(
+            map.resize_room(0, room(1, 1, 6)),
+            Err(RejectReason::Overlap)
+        );
+        assert_eq!(map.resize_room(0, room(1, 1, 4)), Ok(()));
+        assert_eq!(map.at(4, 2), Tile::Floor);
+
+        map.remove_room(0);
+        assert_eq!(map.rooms, vec![room(6, 2, 3)]);
+        assert_eq!(map.room_themes.get(&0), Some(&Theme::Library));
+        assert_eq!(map.at(2, 2), Tile::Wall);
+        assert_eq!(map.at(7, 2), Tile::Floor);
+    }
+
+    #[test]
+    fn vacated_rooms_keep_their_corridors() {
+        // Room with a corridor coming in from the west and a door to the east
+        let mut map = Map::all(14, 7, Tile::Wall);
+        let room = |width| Room {
+            x: 2,
+            y: 1,
+            width,
+            height: 5,
+        };
+        map.add_room(room(5));
+        *map.at_mut(1, 3) = Tile::Floor;
+        *map.at_mut(7, 3) = Tile::ClosedDoor;
+        *map.at_mut(3, 2) = Tile::ShallowWater;
+
+        assert_eq!(map.resize_room(0, room(2)), Ok(()));
+        assert_eq!(map.at(3, 2), Tile::ShallowWater);
+        assert_eq!(map.at(5, 1), Tile::Wall);
+        assert_eq!(map.at(5, 3), Tile::Floor);
+        assert_eq!(map.at(7, 3), Tile::ClosedDoor);
+
+        assert_eq!(map.resize_room(0, room(5)), Ok(()));
+        assert_eq!(map.at(5, 1), Tile::Floor);
+        assert_eq!(map.at(3, 2), Tile::ShallowWater);
+
+        map.remove_room(0);
+        assert!((1..7).all(|x| map.at(x, 3) != Tile::Wall));
+        assert_eq!(map.at(4, 2), Tile::Wall);
+        assert_eq!(map.at(3, 2), Tile::Wall);
+    }
+
+    #[test]
+    fn free_tiles_are_not_blocked() {
+        let map = Map::all(3, 1, Tile::Floor);
+        let blocked: HashSet<usize> = vec![0, 2].into_iter().collect();
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..10 {
+            assert_eq!(
+                map.random_free_tile(&blocked, &mut rng),
+                Some(Point::new(1, 0))
+            );
+        }
+        let blocked: HashSet<usize> = (0..3).collect();
+        assert_eq!(map.random_free_tile(&blocked, &mut rng), None);
+    }
+
     #[test]
     fn idx_xy_inv() {
         // TODO: eri qui CICCIO.
@@ -0,0 +1,288 @@
+use crate::map::base::{Map, Room, Tile};
+use crate::map::history::History;
+use crate::utils::dir::Dir;
+use crate::utils::rect::Rect;
+use rltk::Point;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::{self, BufRead, Write};
+
+/// Why a room couldn't be placed
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Copy, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectReason {
+    /// The room would touch (or cross) the map border
+    OutOfBounds,
+    /// The room would overlap an already placed one
+    Overlap,
+}
+
+/// Something that happened while generating a map
+///
+/// Events carry everything needed to rebuild the map (see `replay`).
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(tag = "event")]
+pub enum GenEvent {
+    /// A `width` x `height` map filled with `fill` was created
+    Started {
+        generator: String,
+        seed: Option<u64>,
+        width: usize,
+        height: usize,
+        fill: Tile,
+        /// Whether tiles start lit
+        lit: bool,
+    },
+    /// The tile at (`x`, `y`) was changed (corridors being dug, doors placed...)
+    TileSet { x: i32, y: i32, tile: Tile },
+    /// `room` was carved into the map
+    RoomPlaced { room: Room, lit: bool },
+    /// `room` couldn't be carved into the map
+    RoomRejected { room: Room, reason: RejectReason },
+    /// The corridor being dug from (`x`, `y`) changed direction
+    CorridorTurn { x: i32, y: i32, dir: Dir },
+    /// The generator gave up at (`x`, `y`), leaving a partial map
+    Aborted { x: i32, y: i32 },
+    /// The generator is done
+    Finished,
+}
+
+impl GenEvent {
+    /// Where the event happened (if anywhere in particular)
+    pub fn position(&self) -> Option<Point> {
+        match self {
+            GenEvent::TileSet { x, y, .. }
+            | GenEvent::CorridorTurn { x, y, .. }
+            | GenEvent::Aborted { x, y } => Some(Point::new(*x, *y)),
+            GenEvent::RoomPlaced { room, .. } | GenEvent::RoomRejected { room, .. } => Some(
+                Point::new(room.x + room.width / 2, room.y + room.height / 2),
+            ),
+            GenEvent::Started { .. } | GenEvent::Finished => None,
+        }
+    }
+}
+
+/// Gets notified of what generators do
+pub trait GenObserver {
+    /// Called right after `event` happened, `map` being the map as it is now
+    fn notify(&mut self, event: &GenEvent, map: &Map);
+}
+
+/// Nobody's listening
+impl GenObserver for () {
+    fn notify(&mut self, _event: &GenEvent, _map: &Map) {}
+}
+
+impl<T: GenObserver + ?Sized> GenObserver for &mut T {
+    fn notify(&mut self, event: &GenEvent, map: &Map) {
+        (**self).notify(event, map);
+    }
+}
+
+/// Both observers get notified
+impl<A: GenObserver, B: GenObserver> GenObserver for (A, B) {
+    fn notify(&mut self, event: &GenEvent, map: &Map) {
+        self.0.notify(event, map);
+        self.1.notify(event, map);
+    }
+}
+
+/// Histories take a snapshot after each event
+impl GenObserver for History {
+    fn notify(&mut self, event: &GenEvent, map: &Map) {
+        self.record(map, event.position());
+    }
+}
+
+/// Writes events as JSON, one per line
+///
+/// Writing stops at the first error, which `finish` reports.
+pub struct JsonLinesSink<W: Write> {
+    out: W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> JsonLinesSink<W> {
+    /// New sink writing to `out`
+    pub fn new(out: W) -> Self {
+        JsonLinesSink { out, error: None }
+    }
+
+    /// Flushes what was written, telling whether everything went fine
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => self.out.flush(),
+        }
+    }
+}
+
+impl<W: Write> GenObserver for JsonLinesSink<W> {
+    fn notify(&mut self, event: &GenEvent, _map: &Map) {
+        if self.error.is_some() {
+            return;
+        }
+        let out = &mut self.out;
+        let written = serde_json::to_writer(&mut *out, event)
+            .map_err(io::Error::from)
+            .and_then(|()| writeln!(out));
+        self.error = written.err();
+    }
+}
+
+/// Read events written by a `JsonLinesSink` (empty lines are skipped)
+pub fn read_json_lines<R: BufRead>(input: R) -> Result<Vec<GenEvent>, Box<dyn Error>> {
+    let mut events = vec![];
+    for line in input.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            events.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(events)
+}
+
+/// Rebuilds a map from the events of its generation, notifying `observer` along the way
+pub fn replay(events: &[GenEvent], observer: &mut dyn GenObserver) -> Result<Map, String> {
+    let mut map = match events.first() {
+        Some(GenEvent::Started {
+            width,
+            height,
+            fill,
+            lit,
+            ..
+        }) => {
+            let mut map = Map::all(*width, *height, *fill);
+            map.lit.iter_mut().for_each(|l| *l = *lit);
+            map
+        }
+        _ => return Err("Generation events must begin with a start event".to_string()),
+    };
+
+    for event in events {
+        match event {
+            GenEvent::TileSet { x, y, tile } => {
+                if !map.contains_point(*x, *y) {
+                    return Err(format!("Tile ({}, {}) is outside the map", x, y));
+                }
+                *map.at_mut(*x, *y) = *tile;
+            }
+            GenEvent::RoomPlaced { room, lit } => {
+                map.try_add_room(room.clone())
+                    .map_err(|reason| format!("Can't place {:?}: {:?}", room, reason))?;
+                if *lit {
+                    map.light_room(map.rooms.len() - 1);
+                }
+            }
+            GenEvent::Aborted { .. } | GenEvent::Finished => map.compute_regions(),
+            GenEvent::Started { .. }
+            | GenEvent::RoomRejected { .. }
+            | GenEvent::CorridorTurn { .. } => {}
+        }
+        observer.notify(event, &map);
+    }
+
+    Ok(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::gen::rnc;
+
+    /// Records every event it gets
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<GenEvent>,
+    }
+
+    impl GenObserver for Recorder {
+        fn notify(&mut self, event: &GenEvent, _map: &Map) {
+            self.events.push(event.clone());
+        }
+    }
+
+    #[test]
+    fn events_survive_json_lines() {
+        let events = vec![
+            GenEvent::TileSet {
+                x: 1,
+                y: 2,
+                tile: Tile::ClosedDoor,
+            },
+            GenEvent::RoomRejected {
+                room: Room {
+                    x: 0,
+                    y: 0,
+                    width: 3,
+                    height: 3,
+                },
+                reason: RejectReason::OutOfBounds,
+            },
+            GenEvent::Finished,
+        ];
+        let mut sink = JsonLinesSink::new(vec![]);
+        for event in &events {
+            sink.notify(event, &Map::empty(1, 1));
+        }
+
+        let text = String::from_utf8(sink.out).unwrap();
+        assert!(text
+            .lines()
+            .next()
+            .unwrap()
+            .contains(r#""event":"TileSet""#));
+        assert_eq!(read_json_lines(text.as_bytes()).unwrap(), events);
+    }
+
+    #[test]
+    fn write_errors_are_kept_for_the_end() {
+        let mut buf = [0; 8];
+        let mut sink = JsonLinesSink::new(&mut buf[..]);
+        for _ in 0..3 {
+            sink.notify(&GenEvent::Finished, &Map::empty(1, 1));
+        }
+
+        assert!(sink.finish().is_err());
+        assert!(JsonLinesSink::new(vec![]).finish().is_ok());
+    }
+
+    #[test]
+    fn replay_rebuilds_generated_map() {
+        let conf = rnc::ConfigBuilder::default()
+            .map_width(40)
+            .map_height(30)
+            .iterations(300)
+            .build()
+            .unwrap();
+        let mut recorder = Recorder::default();
+        let map = rnc::make_map_observed(conf, &mut recorder).unwrap_or_else(|map| map);
+
+        let replayed = replay(&recorder.events, &mut ()).unwrap();
+
+        assert_eq!(replayed.tiles, map.tiles);
+        assert_eq!(replayed.lit, map.lit);
+        assert_eq!(replayed.rooms, map.rooms);
+        assert_eq!(replayed.room_links, map.room_links);
+    }
+
+    #[test]
+    fn rejected_rooms_tell_why() {
+        let mut map = Map::all(20, 20, Tile::Wall);
+        let room = |x, y| Room {
+            x,
+            y,
+            width: 5,
+            height: 5,
+        };
+
+        assert_eq!(map.try_add_room(room(2, 2)), Ok(()));
+        assert_eq!(map.try_add_room(room(4, 4)), Err(RejectReason::Overlap));
+        assert_eq!(map.try_add_room(room(0, 0)), Err(RejectReason::OutOfBounds));
+    }
+
+    #[test]
+    fn replay_needs_a_start() {
+        assert!(replay(&[GenEvent::Finished], &mut ()).is_err());
+    }
+}
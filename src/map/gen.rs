@@ -1,37 +1,121 @@
+use crate::consts::{MAP_HEIGHT, MAP_WIDTH};
 use crate::map::base::*;
+use crate::map::events::{GenEvent, GenObserver};
 use crate::utils::{
     dir::{Advance, Dir},
     rect::*,
 };
 use derive_builder::Builder;
 use log::{error, info, warn};
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use std::error::Error;
+
+/// Random number generator seeded with `seed` (or with entropy, if there is none)
+fn seeded_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+/// Sets the tile at (`x`, `y`), letting `observer` know
+fn set_tile(map: &mut Map, x: i32, y: i32, tile: Tile, observer: &mut dyn GenObserver) {
+    *map.at_mut(x, y) = tile;
+    observer.notify(&GenEvent::TileSet { x, y, tile }, map);
+}
+
+/// Names of the generators that can be picked with `run`
+pub const GENERATORS: [&str; 2] = ["rnc", "ugly"];
+
+/// Outcome of a generator run
+pub struct Generated {
+    pub map: Map,
+    /// False if the generator stopped prematurely (returning a partial map)
+    pub complete: bool,
+}
+
+/// Runs the generator called `name` (the ugly one only uses the map size of `conf`)
+pub fn run(
+    name: &str,
+    conf: rnc::Config,
+    seed: u64,
+    observer: &mut dyn GenObserver,
+) -> Result<Generated, String> {
+    match name {
+        "rnc" => {
+            let (map, complete) = match rnc::make_map_observed(conf.with_seed(seed), observer) {
+                Ok(map) => (map, true),
+                Err(map) => (map, false),
+            };
+            Ok(Generated { map, complete })
+        }
+        "ugly" => {
+            let (width, height) = conf.map_size();
+            Ok(Generated {
+                map: make_ugly_map_observed(width, height, Some(seed), observer),
+                complete: true,
+            })
+        }
+        _ => Err(format!(
+            "Unknown generator: {} (available: {})",
+            name,
+            GENERATORS.join(", ")
+        )),
+    }
+}
 
 /// Generate random ugly map
 #[allow(dead_code)]
 pub fn make_ugly_map(width: usize, height: usize) -> Map {
+    make_ugly_map_observed(width, height, None, &mut ())
+}
+
+/// Generate random ugly map from `seed` (if any), telling `observer` about each wall placed
+#[allow(dead_code)]
+pub fn make_ugly_map_observed(
+    width: usize,
+    height: usize,
+    seed: Option<u64>,
+    observer: &mut dyn GenObserver,
+) -> Map {
     let mut res = Map::empty(width, height);
+    observer.notify(
+        &GenEvent::Started {
+            generator: "ugly".to_string(),
+            seed,
+            width,
+            height,
+            fill: Tile::Floor,
+            lit: true,
+        },
+        &res,
+    );
 
     // Border walls
-    for i in 0..width {
-        *res.at_mut(i, 0) = Tile::Wall;
-        *res.at_mut(i, height - 1) = Tile::Wall;
+    let (w, h) = (width as i32, height as i32);
+    for i in 0..w {
+        set_tile(&mut res, i, 0, Tile::Wall, observer);
+        set_tile(&mut res, i, h - 1, Tile::Wall, observer);
     }
-    for i in 0..height {
-        *res.at_mut(0, i) = Tile::Wall;
-        *res.at_mut(width - 1, i) = Tile::Wall;
+    for i in 0..h {
+        set_tile(&mut res, 0, i, Tile::Wall, observer);
+        set_tile(&mut res, w - 1, i, Tile::Wall, observer);
     }
 
     // Generate some random walls
     // TODO: try to not generate walls over the player
-    let mut rng = rltk::RandomNumberGenerator::new();
+    let mut rng = seeded_rng(seed);
     for _ in 0..400 {
-        let x = rng.roll_dice(1, (width - 1) as i32);
-        let y = rng.roll_dice(1, (height - 1) as i32);
+        let x = rng.gen_range(1, w);
+        let y = rng.gen_range(1, h);
 
-        *res.at_mut(x, y) = Tile::Wall;
+        set_tile(&mut res, x, y, Tile::Wall, observer);
     }
 
+    res.compute_regions();
+    observer.notify(&GenEvent::Finished, &res);
     res
 }
 
@@ -39,16 +123,25 @@ pub fn make_ugly_map(width: usize, height: usize) -> Map {
 pub mod rnc {
     use super::*;
 
-    #[derive(Builder)]
+    #[derive(Builder, Clone, Deserialize)]
     #[builder(default)]
+    #[serde(default)]
     pub struct Config {
         map_width: usize,
         map_height: usize,
         room_chance: f32,
         turn_chance: f32,
+        /// Percent chance for a room to be lit (corridors are always dark)
+        lit_room_chance: f32,
+        /// Percent chance for a doorway (where a corridor enters a room) to get a door
+        door_chance: f32,
+        /// Percent chance for a door to be locked
+        locked_door_chance: f32,
         min_room_size: u32,
         max_room_size: u32,
         iterations: u32,
+        /// Seed of the random number generator (a random one is used if missing)
+        seed: Option<u64>,
         // TODO: add corridor length and room connection options
         // NB. for now all rooms are connected
     }
@@ -60,21 +153,82 @@ pub mod rnc {
                 map_height: 100,
                 room_chance: 1.,
                 turn_chance: 1.,
+                lit_room_chance: 70.,
+                door_chance: 60.,
+                locked_door_chance: 10.,
                 min_room_size: 4,
                 max_room_size: 10,
                 iterations: 1000,
+                seed: None,
             }
         }
     }
 
+    impl Config {
+        /// Config used by the game (maps fill the screen above the UI)
+        pub fn game() -> Self {
+            Config {
+                map_width: MAP_WIDTH,
+                map_height: MAP_HEIGHT,
+                ..Config::default()
+            }
+        }
+
+        /// Width and height of the maps
+        pub fn map_size(&self) -> (usize, usize) {
+            (self.map_width, self.map_height)
+        }
+
+        /// Seed of the random number generator (if any)
+        pub fn seed(&self) -> Option<u64> {
+            self.seed
+        }
+
+        /// Same config, with the random number generator seeded with `seed`
+        pub fn with_seed(self, seed: u64) -> Self {
+            Config {
+                seed: Some(seed),
+                ..self
+            }
+        }
+
+        /// Parse config from its YAML representation (missing fields get their default value)
+        pub fn parse(src: &str) -> Result<Self, Box<dyn Error>> {
+            Ok(serde_yaml::from_str(src)?)
+        }
+
+        /// Load config from file
+        pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+            Self::parse(&std::fs::read_to_string(path)?)
+        }
+    }
+
     /// Create new simple map with rooms and corridors (Moria style)
     pub fn make_map(conf: Config) -> Result<Map, Map> {
+        make_map_observed(conf, &mut ())
+    }
+
+    /// Same as `make_map`, telling `observer` about each step
+    pub fn make_map_observed(conf: Config, observer: &mut dyn GenObserver) -> Result<Map, Map> {
         info!("STARTING NEW R&C DUNGEON GENERATION PROCESS");
 
         // Start with map filled with walls
         let mut res = Map::all(conf.map_width, conf.map_height, Tile::Wall);
+        // Everything is dark until lit rooms get carved.
+        res.lit.iter_mut().for_each(|l| *l = false);
+        observer.notify(
+            &GenEvent::Started {
+                generator: "rnc".to_string(),
+                seed: conf.seed,
+                width: conf.map_width,
+                height: conf.map_height,
+                fill: Tile::Wall,
+                lit: false,
+            },
+            &res,
+        );
 
-        let mut rng = rand::thread_rng();
+        let mut rng = seeded_rng(conf.seed);
 
         // Corridor starting state (random/arbitrary).
         let mut cur_dir = Dir::South;
@@ -84,7 +238,7 @@ pub mod rnc {
         // Start creating rooms and corridors!
         for _ in 0..conf.iterations {
             // Carve corridor.
-            *res.at_mut(cur_x, cur_y) = Tile::Floor;
+            set_tile(&mut res, cur_x, cur_y, Tile::Floor, observer);
 
             // Generate room if chances are right.
             if rng.gen_range(0., 100.) < conf.room_chance {
@@ -110,13 +264,37 @@ pub mod rnc {
                     "Spawning room: {:?} [corridor pos: ({}, {})].",
                     new_room, cur_x, cur_y
                 );
-                if !res.add_room(new_room) {
-                    warn!("FAILED room spawn, skipping...");
+                match res.try_add_room(new_room.clone()) {
+                    Err(reason) => {
+                        warn!("FAILED room spawn ({:?}), skipping...", reason);
+                        let event = GenEvent::RoomRejected {
+                            room: new_room,
+                            reason,
+                        };
+                        observer.notify(&event, &res);
+                    }
+                    Ok(()) => {
+                        let lit = rng.gen_range(0., 100.) < conf.lit_room_chance;
+                        if lit {
+                            res.light_room(res.rooms.len() - 1);
+                        }
+                        let event = GenEvent::RoomPlaced {
+                            room: new_room,
+                            lit,
+                        };
+                        observer.notify(&event, &res);
+                    }
                 }
             }
             // Change corridor generation direction if chances are right.
             else if rng.gen_range(0., 100.) < conf.turn_chance {
                 cur_dir = Dir::cycle(cur_dir);
+                let event = GenEvent::CorridorTurn {
+                    x: cur_x,
+                    y: cur_y,
+                    dir: cur_dir,
+                };
+                observer.notify(&event, &res);
                 info!("Corridor turn roll successful. New corridor advancement direction: {:?} [corridor pos: ({}, {})].", cur_dir, cur_x, cur_y);
             }
 
@@ -126,6 +304,10 @@ pub mod rnc {
                 // TODO: Give option to create dead ends.
                 if tries == 3 {
                     warn!("Too many corridor advancement attempts... returning partial map.");
+                    place_doors(&mut res, &conf, &mut rng, observer);
+                    res.compute_regions();
+                    let event = GenEvent::Aborted { x: cur_x, y: cur_y };
+                    observer.notify(&event, &res);
                     return Err(res);
                 }
 
@@ -147,6 +329,12 @@ pub mod rnc {
                 if corridor_oob {
                     info!("Corridor failed to advance (OOB)!");
                     cur_dir = cur_dir.cycle();
+                    let event = GenEvent::CorridorTurn {
+                        x: cur_x,
+                        y: cur_y,
+                        dir: cur_dir,
+                    };
+                    observer.notify(&event, &res);
                     continue;
                 }
 
@@ -166,7 +354,107 @@ pub mod rnc {
             }
         }
 
+        place_doors(&mut res, &conf, &mut rng, observer);
+        res.compute_regions();
+        observer.notify(&GenEvent::Finished, &res);
+
         // All fine
         Ok(res)
     }
+
+    /// Checks if (`x`, `y`) is a corridor tile entering a room through one of its edges
+    ///
+    /// `vertical` tells whether the corridor goes north-south (i.e. it enters through the top or
+    /// bottom edge), in which case the door has walls east and west of it (north and south
+    /// otherwise).
+    fn is_doorway(map: &Map, x: i32, y: i32, vertical: bool) -> bool {
+        let walls = if vertical {
+            [(x - 1, y), (x + 1, y)]
+        } else {
+            [(x, y - 1), (x, y + 1)]
+        };
+
+        map.at(x, y) == Tile::Floor
+            && !map.rooms.iter().any(|room| room.contains_point(x, y))
+            && walls.iter().all(|&(wx, wy)| map.at(wx, wy) == Tile::Wall)
+    }
+
+    /// Puts doors (some of them locked) where corridors meet room edges
+    fn place_doors<R: Rng>(
+        map: &mut Map,
+        conf: &Config,
+        rng: &mut R,
+        observer: &mut dyn GenObserver,
+    ) {
+        let mut doorways: Vec<(i32, i32)> = vec![];
+        for room in &map.rooms {
+            let (x0, y0, w, h) = (room.x, room.y, room.width, room.height);
+            // Tiles right outside the room edges (corners excluded).
+            let top_bottom = (x0..x0 + w).flat_map(|x| vec![(x, y0 - 1, true), (x, y0 + h, true)]);
+            let sides = (y0..y0 + h).flat_map(|y| vec![(x0 - 1, y, false), (x0 + w, y, false)]);
+
+            doorways.extend(
+                top_bottom
+                    .chain(sides)
+                    .filter(|&(x, y, vertical)| is_doorway(map, x, y, vertical))
+                    .map(|(x, y, _)| (x, y)),
+            );
+        }
+        doorways.sort();
+        doorways.dedup();
+
+        for (x, y) in doorways {
+            if rng.gen_range(0., 100.) >= conf.door_chance {
+                continue;
+            }
+            info!("Placing door at ({}, {}).", x, y);
+            let door = if rng.gen_range(0., 100.) < conf.locked_door_chance {
+                Tile::LockedDoor
+            } else {
+                Tile::ClosedDoor
+            };
+            set_tile(map, x, y, door, observer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Small rnc config with given seed
+    fn seeded_conf(seed: u64) -> rnc::Config {
+        rnc::ConfigBuilder::default()
+            .map_width(40)
+            .map_height(30)
+            .iterations(300)
+            .seed(Some(seed))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn same_seed_same_map() {
+        let first = rnc::make_map(seeded_conf(7)).unwrap_or_else(|map| map);
+        let second = rnc::make_map(seeded_conf(7)).unwrap_or_else(|map| map);
+
+        assert_eq!(first.tiles, second.tiles);
+        assert_eq!(first.rooms.len(), second.rooms.len());
+    }
+
+    #[test]
+    fn ugly_maps_can_be_seeded() {
+        let first = make_ugly_map_observed(20, 10, Some(3), &mut ());
+        let second = make_ugly_map_observed(20, 10, Some(3), &mut ());
+
+        assert_eq!(first.tiles, second.tiles);
+    }
+
+    #[test]
+    fn config_is_parsed_from_yaml() {
+        let conf = rnc::Config::parse("map_width: 50\nseed: 12").unwrap();
+
+        assert_eq!(conf.seed(), Some(12));
+        assert_eq!(conf.with_seed(3).seed(), Some(3));
+    }
 }
@@ -0,0 +1,130 @@
+use crate::map::base::{Map, Tile};
+use rltk::{Console, Point, Rltk, RGB};
+
+/// State of a map at some step of its generation
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub width: usize,
+    pub tiles: Vec<Tile>,
+    /// Where the generator was working (e.g. the head of the corridor being dug)
+    pub cursor: Option<Point>,
+}
+
+impl Snapshot {
+    /// Draw the snapshot (the whole of it, there's no field of view here)
+    pub fn draw(&self, ctx: &mut Rltk) {
+        for (idx, tile) in self.tiles.iter().enumerate() {
+            let (x, y) = ((idx % self.width) as i32, (idx / self.width) as i32);
+            let fg = match tile {
+                Tile::Wall => RGB::named(rltk::GREY),
+                _ => RGB::named(rltk::WHITE),
+            };
+            let bg = if self.cursor == Some(Point::new(x, y)) {
+                RGB::named(rltk::MAGENTA)
+            } else {
+                RGB::named(rltk::BLACK)
+            };
+            ctx.set(x, y, fg, bg, rltk::to_cp437(tile.glyph()));
+        }
+    }
+}
+
+/// Snapshots of a map taken after each step of its generation (histories observe generators)
+///
+/// A default history doesn't record anything.
+#[derive(Default)]
+pub struct History {
+    recording: bool,
+    snapshots: Vec<Snapshot>,
+}
+
+impl History {
+    /// New history that records snapshots
+    pub fn recording() -> Self {
+        History {
+            recording: true,
+            snapshots: vec![],
+        }
+    }
+
+    /// Checks if snapshots are being recorded
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    /// Take a snapshot of `map` (unless nothing changed since the last one)
+    pub fn record(&mut self, map: &Map, cursor: Option<Point>) {
+        if !self.recording {
+            return;
+        }
+        if let Some(last) = self.snapshots.last() {
+            if last.cursor == cursor && last.tiles == map.tiles {
+                return;
+            }
+        }
+
+        self.snapshots.push(Snapshot {
+            width: map.width,
+            tiles: map.tiles.clone(),
+            cursor,
+        });
+    }
+
+    /// Number of recorded snapshots
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    /// Checks if nothing was recorded
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    /// Snapshot taken at `step` (if any)
+    pub fn get(&self, step: usize) -> Option<&Snapshot> {
+        self.snapshots.get(step)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::gen::rnc;
+
+    #[test]
+    fn default_history_records_nothing() {
+        let mut history = History::default();
+        history.record(&Map::empty(3, 3), None);
+
+        assert!(history.is_empty());
+    }
+
+    #[test]
+    fn unchanged_maps_are_recorded_once() {
+        let mut map = Map::empty(3, 3);
+        let mut history = History::recording();
+        history.record(&map, None);
+        history.record(&map, None);
+        *map.at_mut(1, 1) = Tile::Wall;
+        history.record(&map, None);
+        history.record(&map, Some(Point::new(0, 0)));
+
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.get(1).unwrap().tiles[4], Tile::Wall);
+    }
+
+    #[test]
+    fn last_snapshot_is_the_generated_map() {
+        let mut history = History::recording();
+        let conf = rnc::ConfigBuilder::default()
+            .map_width(40)
+            .map_height(30)
+            .iterations(200)
+            .build()
+            .unwrap();
+        let map = rnc::make_map_observed(conf, &mut history).unwrap_or_else(|map| map);
+
+        assert!(history.len() > 1);
+        assert_eq!(history.get(history.len() - 1).unwrap().tiles, map.tiles);
+    }
+}
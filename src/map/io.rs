@@ -0,0 +1,425 @@
+use crate::map::base::{Map, Room, Tile};
+use crate::map::regions::RoomTag;
+use crate::map::theming::Theme;
+use serde::de::{self, DeserializeOwned, Deserializer};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+
+/// What gets written to map files
+///
+/// Per-tile data is stored as one string per row, so that RON files can be edited by hand.
+/// Regions are recomputed on load, while light and movement rules don't belong to the map file.
+#[derive(Serialize, Deserialize)]
+struct MapData {
+    width: usize,
+    height: usize,
+    tiles: Vec<String>,
+    /// Lit tiles are marked with `*`, the others with `.`
+    lit: Vec<String>,
+    /// Revealed tiles are marked with `*`, the others with `.` (missing means nothing revealed)
+    #[serde(default)]
+    revealed: Vec<String>,
+    rooms: Vec<Room>,
+    #[serde(default)]
+    room_tags: BTreeMap<usize, BTreeSet<RoomTag>>,
+    #[serde(default)]
+    room_themes: BTreeMap<usize, Theme>,
+}
+
+/// Character standing for `tile` in map files (unlike glyphs, each tile has its own)
+fn tile_char(tile: Tile) -> char {
+    match tile {
+        Tile::LockedDoor => '=',
+        _ => tile.glyph(),
+    }
+}
+
+/// Tile `c` stands for in map files
+fn char_tile(c: char) -> Option<Tile> {
+    Some(match c {
+        '#' => Tile::Wall,
+        '.' => Tile::Floor,
+        '+' => Tile::ClosedDoor,
+        '\'' => Tile::OpenDoor,
+        '=' => Tile::LockedDoor,
+        '&' => Tile::Bookshelf,
+        '~' => Tile::ShallowWater,
+        '"' => Tile::TallGrass,
+        _ => return None,
+    })
+}
+
+/// One string per row of `values`, each value written as `to_char` says
+fn to_rows<T: Copy>(values: &[T], width: usize, to_char: impl Fn(T) -> char) -> Vec<String> {
+    values
+        .chunks(width)
+        .map(|row| row.iter().map(|&v| to_char(v)).collect())
+        .collect()
+}
+
+/// Values in `rows` (which must be `width` x `height`), each char parsed with `from_char`
+fn from_rows<T>(
+    rows: &[String],
+    width: usize,
+    height: usize,
+    what: &str,
+    from_char: impl Fn(char) -> Option<T>,
+) -> Result<Vec<T>, String> {
+    if rows.len() != height {
+        return Err(format!(
+            "Expected {} rows of {}, got {}",
+            height,
+            what,
+            rows.len()
+        ));
+    }
+
+    // Check sizes first so that bogus dimensions can't make us allocate more than was read.
+    for (y, row) in rows.iter().enumerate() {
+        if row.chars().count() != width {
+            return Err(format!("Row {} of {} is not {} wide", y, what, width));
+        }
+    }
+
+    let mut values = Vec::with_capacity(width * height);
+    for (y, row) in rows.iter().enumerate() {
+        for c in row.chars() {
+            values.push(
+                from_char(c).ok_or_else(|| format!("Unknown {} '{}' in row {}", what, c, y))?,
+            );
+        }
+    }
+    Ok(values)
+}
+
+/// `*` for set flags, `.` for the others
+fn flag_char(flag: bool) -> char {
+    if flag {
+        '*'
+    } else {
+        '.'
+    }
+}
+
+/// Flag `c` stands for
+fn char_flag(c: char) -> Option<bool> {
+    match c {
+        '*' => Some(true),
+        '.' => Some(false),
+        _ => None,
+    }
+}
+
+/// Checks that `room` is a non-empty rect lying inside a `width` x `height` map
+fn check_room(room: &Room, width: usize, height: usize) -> Result<(), String> {
+    if room.width <= 0 || room.height <= 0 {
+        return Err(format!("{:?} has no tiles", room));
+    }
+
+    // Mind overflows: rooms come straight from the file.
+    let fits = |start: i32, len: i32, max: usize| {
+        start >= 0
+            && start
+                .checked_add(len)
+                .map_or(false, |end| end as usize <= max)
+    };
+    if !fits(room.x, room.width, width) || !fits(room.y, room.height, height) {
+        return Err(format!("{:?} is outside the map", room));
+    }
+    Ok(())
+}
+
+impl From<&Map> for MapData {
+    fn from(map: &Map) -> Self {
+        MapData {
+            width: map.width,
+            height: map.height,
+            tiles: to_rows(&map.tiles, map.width, tile_char),
+            lit: to_rows(&map.lit, map.width, flag_char),
+            revealed: to_rows(&map.revealed, map.width, flag_char),
+            rooms: map.rooms.clone(),
+            room_tags: map
+                .room_tags
+                .iter()
+                .map(|(&room, tags)| (room, tags.iter().copied().collect()))
+                .collect(),
+            room_themes: map.room_themes.iter().map(|(&r, &t)| (r, t)).collect(),
+        }
+    }
+}
+
+impl MapData {
+    /// Builds the map described by the data, checking it makes sense
+    fn into_map(self) -> Result<Map, String> {
+        let (width, height) = (self.width, self.height);
+        if width == 0 || height == 0 {
+            return Err(format!("Map can't be {}x{}", width, height));
+        }
+        width
+            .checked_mul(height)
+            .filter(|&size| size <= i32::MAX as usize)
+            .ok_or_else(|| format!("Map is too big ({}x{})", width, height))?;
+
+        // Only allocate the map once the tiles prove the dimensions are genuine.
+        let tiles = from_rows(&self.tiles, width, height, "tile", char_tile)?;
+        let mut map = Map::all(width, height, Tile::Wall);
+        map.tiles = tiles;
+        map.lit = from_rows(&self.lit, width, height, "light flag", char_flag)?;
+        if !self.revealed.is_empty() {
+            map.revealed = from_rows(&self.revealed, width, height, "revealed flag", char_flag)?;
+        }
+
+        for room in &self.rooms {
+            check_room(room, width, height)?;
+        }
+        for &room in self.room_tags.keys().chain(self.room_themes.keys()) {
+            if room >= self.rooms.len() {
+                return Err(format!("There is no room {}", room));
+            }
+        }
+        map.rooms = self.rooms;
+        map.room_tags = self
+            .room_tags
+            .into_iter()
+            .map(|(room, tags)| (room, tags.into_iter().collect()))
+            .collect();
+        map.room_themes = self.room_themes.into_iter().collect();
+
+        map.compute_regions();
+        Ok(map)
+    }
+}
+
+/// Maps are written as `MapData`
+impl Serialize for Map {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MapData::from(self).serialize(serializer)
+    }
+}
+
+/// Maps are read as `MapData` (and checked)
+impl<'de> Deserialize<'de> for Map {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        MapData::deserialize(deserializer)?
+            .into_map()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Save `value` to file (as RON if the file name ends in `.ron`, in binary otherwise)
+pub fn save<T: Serialize>(value: &T, path: &str) -> Result<(), Box<dyn Error>> {
+    if path.ends_with(".ron") {
+        let pretty = ron::ser::PrettyConfig::default();
+        std::fs::write(path, ron::ser::to_string_pretty(value, pretty)?)?;
+    } else {
+        std::fs::write(path, bincode::serialize(value)?)?;
+    }
+    Ok(())
+}
+
+/// Load value saved with `save`
+pub fn load<T: DeserializeOwned>(path: &str) -> Result<T, Box<dyn Error>> {
+    if path.ends_with(".ron") {
+        Ok(ron::de::from_str(&std::fs::read_to_string(path)?)?)
+    } else {
+        Ok(bincode::deserialize(&std::fs::read(path)?)?)
+    }
+}
+
+impl Map {
+    /// Human readable (RON) representation of the map
+    pub fn to_ron(&self) -> Result<String, Box<dyn Error>> {
+        let pretty = ron::ser::PrettyConfig::default();
+        Ok(ron::ser::to_string_pretty(self, pretty)?)
+    }
+
+    /// Parse map from its RON representation
+    pub fn from_ron(src: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(ron::de::from_str(src)?)
+    }
+
+    /// Compact binary representation of the map
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(bincode::serialize(self)?)
+    }
+
+    /// Parse map from its binary representation
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+
+    /// Save map to file (as RON if the file name ends in `.ron`, in binary otherwise)
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        save(self, path)
+    }
+
+    /// Load map saved with `Map::save`
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        load(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two lit rooms joined by a corridor with a locked door, plus some tags and a theme
+    fn test_map() -> Map {
+        let mut map = Map::all(12, 5, Tile::Wall);
+        map.lit.iter_mut().for_each(|l| *l = false);
+        for &x in &[1, 8] {
+            map.add_room(Room {
+                x,
+                y: 1,
+                width: 3,
+                height: 3,
+            });
+            map.light_room(map.rooms.len() - 1);
+        }
+        for x in 4..8 {
+            *map.at_mut(x, 2) = Tile::Floor;
+        }
+        *map.at_mut(4, 2) = Tile::LockedDoor;
+        *map.at_mut(9, 2) = Tile::ShallowWater;
+        map.revealed[13] = true;
+        map.tag_room(0, RoomTag::Start);
+        map.tag_room(1, RoomTag::Boss);
+        map.room_themes.insert(1, Theme::Flooded);
+        map.compute_regions();
+        map
+    }
+
+    /// Checks that everything saved survived the trip
+    fn assert_same(a: &Map, b: &Map) {
+        assert_eq!((a.width, a.height), (b.width, b.height));
+        assert_eq!(a.tiles, b.tiles);
+        assert_eq!(a.lit, b.lit);
+        assert_eq!(a.revealed, b.revealed);
+        assert_eq!(a.rooms, b.rooms);
+        assert_eq!(a.room_tags, b.room_tags);
+        assert_eq!(a.room_themes, b.room_themes);
+        assert_eq!(a.regions, b.regions);
+        assert_eq!(a.room_links, b.room_links);
+    }
+
+    #[test]
+    fn ron_round_trip() {
+        let map = test_map();
+        let src = map.to_ron().unwrap();
+
+        assert!(src.contains(r##""#...=....~.#""##));
+        assert_same(&map, &Map::from_ron(&src).unwrap());
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let map = test_map();
+
+        assert_same(&map, &Map::from_bytes(&map.to_bytes().unwrap()).unwrap());
+    }
+
+    #[test]
+    fn hand_written_maps_can_be_loaded() {
+        let src = r#####"(
+            width: 4,
+            height: 3,
+            tiles: ["####", "#.+#", "####"],
+            lit: ["....", ".**.", "...."],
+            rooms: [(x: 1, y: 1, width: 1, height: 1)],
+        )"#####;
+        let map = Map::from_ron(src).unwrap();
+
+        assert_eq!(map.at(2, 1), Tile::ClosedDoor);
+        assert!(map.lit[map.xy_idx(1, 1)]);
+        assert!(map.revealed.iter().all(|&r| !r));
+        assert_eq!(map.room_at(rltk::Point::new(1, 1)), Some(0));
+    }
+
+    #[test]
+    fn broken_maps_are_rejected() {
+        let src = r#####"(
+            width: 4,
+            height: 2,
+            tiles: ["####", "#?.#"],
+            lit: ["....", "...."],
+            rooms: [],
+        )"#####;
+        assert!(Map::from_ron(src).is_err());
+
+        let src = r#####"(
+            width: 4,
+            height: 2,
+            tiles: ["####", "#.#"],
+            lit: ["....", "...."],
+            rooms: [],
+        )"#####;
+        assert!(Map::from_ron(src).is_err());
+
+        let src = r#####"(
+            width: 3,
+            height: 1,
+            tiles: ["#.#"],
+            lit: ["..."],
+            rooms: [(x: 2, y: 0, width: 5, height: 5)],
+        )"#####;
+        assert!(Map::from_ron(src).is_err());
+    }
+
+    #[test]
+    fn bogus_dimensions_are_rejected() {
+        let src = r#####"(
+            width: 0,
+            height: 0,
+            tiles: [],
+            lit: [],
+            rooms: [],
+        )"#####;
+        assert!(Map::from_ron(src).is_err());
+
+        let src = r#####"(
+            width: 100000,
+            height: 100000,
+            tiles: ["#"],
+            lit: ["."],
+            rooms: [],
+        )"#####;
+        assert!(Map::from_ron(src).is_err());
+
+        let src = format!(
+            "(width: {}, height: 2, tiles: [], lit: [], rooms: [])",
+            usize::MAX
+        );
+        assert!(Map::from_ron(&src).is_err());
+    }
+
+    /// 3x3 map with a single room (given as RON)
+    fn map_with_room(room: &str) -> Result<Map, Box<dyn Error>> {
+        Map::from_ron(&format!(
+            r#####"(
+                width: 3,
+                height: 3,
+                tiles: ["###", "#.#", "###"],
+                lit: ["...", "...", "..."],
+                rooms: [{}],
+            )"#####,
+            room
+        ))
+    }
+
+    #[test]
+    fn empty_rooms_are_rejected() {
+        assert!(map_with_room("(x: 1, y: 1, width: 1, height: 1)").is_ok());
+        assert!(map_with_room("(x: 1, y: 1, width: 0, height: 1)").is_err());
+        assert!(map_with_room("(x: 1, y: 1, width: 1, height: -2)").is_err());
+    }
+
+    #[test]
+    fn rooms_outside_the_map_are_rejected() {
+        assert!(map_with_room("(x: -1, y: 1, width: 2, height: 1)").is_err());
+        assert!(map_with_room("(x: 1, y: 2, width: 1, height: 2)").is_err());
+        let room = format!("(x: {}, y: 1, width: {0}, height: 1)", i32::MAX);
+        assert!(map_with_room(&room).is_err());
+    }
+}
@@ -0,0 +1,97 @@
+use crate::map::base::Map;
+use crate::map::io;
+use crate::utils::rect::Rect;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+/// An entity to spawn from a template
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct Placement {
+    /// Template name
+    pub template: String,
+    pub x: i32,
+    pub y: i32,
+}
+
+/// A hand-made (or hand-fixed) level: a map and what's on it
+#[derive(Serialize, Deserialize)]
+pub struct Level {
+    pub map: Map,
+    /// Where the player starts (picked as for generated maps if missing)
+    #[serde(default)]
+    pub start: Option<(i32, i32)>,
+    #[serde(default)]
+    pub entities: Vec<Placement>,
+}
+
+impl Level {
+    /// Checks that the start and everything placed is inside the map
+    fn check(&self) -> Result<(), String> {
+        if let Some((x, y)) = self.start {
+            if !self.map.contains_point(x, y) {
+                return Err(format!("Start ({}, {}) is outside the map", x, y));
+            }
+        }
+        for placement in &self.entities {
+            if !self.map.contains_point(placement.x, placement.y) {
+                return Err(format!("{:?} is outside the map", placement));
+            }
+        }
+        Ok(())
+    }
+
+    /// Save level to file (as RON if the file name ends in `.ron`, in binary otherwise)
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        io::save(self, path)
+    }
+
+    /// Load level saved with `Level::save`
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let level: Level = io::load(path)?;
+        level.check()?;
+        Ok(level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Tile;
+
+    #[test]
+    fn levels_survive_ron() {
+        let mut map = Map::all(5, 4, Tile::Floor);
+        *map.at_mut(0, 0) = Tile::Wall;
+        let level = Level {
+            map,
+            start: Some((1, 2)),
+            entities: vec![Placement {
+                template: "Goblin".to_string(),
+                x: 3,
+                y: 1,
+            }],
+        };
+        let src = ron::ser::to_string(&level).unwrap();
+
+        let loaded: Level = ron::de::from_str(&src).unwrap();
+        assert_eq!(loaded.map.tiles, level.map.tiles);
+        assert_eq!(loaded.start, level.start);
+        assert_eq!(loaded.entities, level.entities);
+        assert!(loaded.check().is_ok());
+    }
+
+    #[test]
+    fn misplaced_entities_are_rejected() {
+        let level = Level {
+            map: Map::empty(3, 3),
+            start: None,
+            entities: vec![Placement {
+                template: "Goblin".to_string(),
+                x: 3,
+                y: 0,
+            }],
+        };
+
+        assert!(level.check().is_err());
+    }
+}
@@ -1,5 +1,14 @@
 pub mod base;
+pub mod events;
 pub mod gen;
+pub mod history;
+pub mod io;
+pub mod level;
+pub mod movement;
+pub mod pathfinding;
+pub mod regions;
 pub mod rltk;
+pub mod stats;
+pub mod theming;
 
 pub use crate::map::base::*;
@@ -0,0 +1,181 @@
+use crate::map::base::{Map, Tile};
+use crate::utils::dir::Dir;
+use crate::utils::rect::Rect;
+use rltk::Point;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// How entities are allowed to walk around the map
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct MovementRules {
+    /// Allow moving diagonally (8-way movement) rather than only along cardinals (4-way)
+    pub diagonals: bool,
+    /// Allow diagonal moves between two walls (i.e. when both orthogonal neighbours are blocked)
+    pub squeeze_diagonally: bool,
+    /// Cost multiplier of diagonal moves
+    pub diagonal_cost: f32,
+    /// Cost of walking onto each kind of tile (overrides `Tile::move_cost`)
+    pub tile_costs: HashMap<Tile, f32>,
+}
+
+impl MovementRules {
+    /// Parse rules from their YAML representation
+    pub fn parse(src: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(serde_yaml::from_str(src)?)
+    }
+
+    /// Load rules from config file
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Cost of walking onto `tile` (`None` if it can't be walked on at all)
+    ///
+    /// Walls can never be walked on, whatever their cost.
+    pub fn tile_cost(&self, tile: Tile) -> Option<f32> {
+        let cost = self
+            .tile_costs
+            .get(&tile)
+            .copied()
+            .unwrap_or_else(|| tile.move_cost());
+
+        Some(cost).filter(|c| tile != Tile::Wall && c.is_finite())
+    }
+}
+
+impl Default for MovementRules {
+    fn default() -> Self {
+        MovementRules {
+            diagonals: true,
+            squeeze_diagonally: true,
+            diagonal_cost: std::f32::consts::SQRT_2,
+            tile_costs: HashMap::new(),
+        }
+    }
+}
+
+impl Map {
+    /// Whether the movement rules allow acting in direction `dir` from `from` (stepping,
+    /// attacking or opening a door), whatever lies there
+    pub fn allows_step(&self, from: Point, dir: Dir) -> bool {
+        let rules = &self.movement;
+        let (dx, dy) = dir.delta();
+        let (x, y) = (from.x + dx, from.y + dy);
+        if !self.contains_point(x, y) {
+            return false;
+        }
+        if !dir.is_diagonal() {
+            return true;
+        }
+
+        // Both orthogonal neighbours are inside the map since the destination is.
+        let squeezing = self.at(x, from.y).is_opaque() && self.at(from.x, y).is_opaque();
+        rules.diagonals && (rules.squeeze_diagonally || !squeezing)
+    }
+
+    /// Whether `to` is one allowed step away from `from`
+    pub fn is_adjacent(&self, from: Point, to: Point) -> bool {
+        let (dx, dy) = (to.x - from.x, to.y - from.y);
+        dx.abs() <= 1
+            && dy.abs() <= 1
+            && Dir::from_delta((dx, dy)).map_or(false, |dir| self.allows_step(from, dir))
+    }
+
+    /// Cost of stepping from `from` in direction `dir` (`None` if the move isn't allowed)
+    pub fn step_cost(&self, from: Point, dir: Dir) -> Option<f32> {
+        if !self.allows_step(from, dir) {
+            return None;
+        }
+        let (dx, dy) = dir.delta();
+        let tile_cost = self.movement.tile_cost(self.at(from.x + dx, from.y + dy))?;
+
+        if dir.is_diagonal() {
+            Some(tile_cost * self.movement.diagonal_cost)
+        } else {
+            Some(tile_cost)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 3x3 map with walls on the north and west sides of the center
+    fn corner_map() -> Map {
+        let mut map = Map::empty(3, 3);
+        *map.at_mut(1, 0) = Tile::Wall;
+        *map.at_mut(0, 1) = Tile::Wall;
+        map
+    }
+
+    #[test]
+    fn no_steps_outside_the_map() {
+        let map = Map::empty(3, 3);
+
+        assert_eq!(map.step_cost(Point::new(0, 0), Dir::West), None);
+        assert_eq!(map.step_cost(Point::new(0, 0), Dir::NorthWest), None);
+        assert_eq!(map.step_cost(Point::new(2, 2), Dir::SouthEast), None);
+        assert!(map.step_cost(Point::new(0, 0), Dir::SouthEast).is_some());
+    }
+
+    #[test]
+    fn diagonals_cost_more_than_cardinals() {
+        let map = Map::empty(3, 3);
+        let center = Point::new(1, 1);
+
+        let cardinal = map.step_cost(center, Dir::North).unwrap();
+        let diagonal = map.step_cost(center, Dir::NorthEast).unwrap();
+
+        assert!((cardinal - 1.).abs() < f32::EPSILON);
+        assert!(diagonal > cardinal);
+    }
+
+    #[test]
+    fn four_way_movement_forbids_diagonals() {
+        let mut map = Map::empty(3, 3);
+        map.movement.diagonals = false;
+
+        assert_eq!(map.step_cost(Point::new(1, 1), Dir::SouthEast), None);
+        assert!(map.step_cost(Point::new(1, 1), Dir::South).is_some());
+    }
+
+    #[test]
+    fn squeezing_between_walls_is_configurable() {
+        let mut map = corner_map();
+        assert!(map.step_cost(Point::new(1, 1), Dir::NorthWest).is_some());
+
+        map.movement.squeeze_diagonally = false;
+        assert_eq!(map.step_cost(Point::new(1, 1), Dir::NorthWest), None);
+    }
+
+    #[test]
+    fn adjacency_follows_the_movement_rules() {
+        let mut map = corner_map();
+        let center = Point::new(1, 1);
+        *map.at_mut(0, 0) = Tile::LockedDoor;
+
+        assert!(map.is_adjacent(center, Point::new(0, 0)));
+        assert!(!map.is_adjacent(center, Point::new(1, 1)));
+        assert!(!map.is_adjacent(Point::new(0, 0), Point::new(2, 2)));
+
+        map.movement.squeeze_diagonally = false;
+        assert!(!map.is_adjacent(center, Point::new(0, 0)));
+        assert!(map.is_adjacent(center, Point::new(2, 2)));
+
+        map.movement.diagonals = false;
+        assert!(!map.is_adjacent(center, Point::new(2, 2)));
+        assert!(map.is_adjacent(center, Point::new(2, 1)));
+    }
+
+    #[test]
+    fn tile_costs_can_be_overridden() {
+        let rules = MovementRules::parse("tile_costs: { floor: 2.5 }").unwrap();
+
+        assert_eq!(rules.tile_cost(Tile::Floor), Some(2.5));
+        assert_eq!(rules.tile_cost(Tile::Wall), None);
+        assert!(rules.diagonals);
+    }
+}
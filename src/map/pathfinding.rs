@@ -0,0 +1,410 @@
+use crate::map::base::Map;
+use rltk::{BaseMap, Point};
+use std::cmp::Ordering;
+use std::collections::hash_map::Entry;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+/// Flee maps are distance maps scaled by this (negative) factor and then smoothed again, so
+/// that running away prefers open areas over the nearest dead end.
+const FLEE_FACTOR: f32 = -1.2;
+
+/// Entry of the open set (ordered so that the cheapest one gets popped first)
+#[derive(PartialEq)]
+struct Node {
+    cost: f32,
+    idx: usize,
+}
+
+impl Eq for Node {}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .partial_cmp(&self.cost)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Walkable neighbours of `idx` (with their cost), skipping blocked tiles unless it is `goal`
+fn exits<'a>(
+    map: &Map,
+    blocked: &'a HashSet<usize>,
+    idx: usize,
+    goal: Option<usize>,
+) -> impl Iterator<Item = (usize, f32)> + 'a {
+    map.get_available_exits(idx)
+        .into_iter()
+        .filter(move |&(next, _)| Some(next) == goal || !blocked.contains(&next))
+}
+
+/// Cheapest path from `start` to `end` (excluding `start`, including `end`)
+///
+/// `end` can be reached even if it is blocked (e.g. to walk up to someone).
+pub fn a_star(map: &Map, blocked: &HashSet<usize>, start: usize, end: usize) -> Option<Vec<usize>> {
+    let goal = map.idx_xy(end);
+    let heuristic = |idx: usize| rltk::DistanceAlg::Pythagoras.distance2d(map.idx_xy(idx), goal);
+
+    let mut cost = HashMap::new();
+    let mut came_from = HashMap::new();
+    let mut open = BinaryHeap::new();
+    cost.insert(start, 0.);
+    open.push(Node {
+        cost: heuristic(start),
+        idx: start,
+    });
+
+    while let Some(Node { idx, .. }) = open.pop() {
+        if idx == end {
+            // Walk back to the start.
+            let mut path = vec![end];
+            let mut cur = end;
+            while let Some(&prev) = came_from.get(&cur) {
+                if prev == start {
+                    break;
+                }
+                path.push(prev);
+                cur = prev;
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        let here = cost[&idx];
+        for (next, step) in exits(map, blocked, idx, Some(end)) {
+            let new_cost = here + step;
+            if cost.get(&next).map_or(true, |&old| new_cost < old) {
+                cost.insert(next, new_cost);
+                came_from.insert(next, idx);
+                open.push(Node {
+                    cost: new_cost + heuristic(next),
+                    idx: next,
+                });
+            }
+        }
+    }
+
+    None
+}
+
+/// Cost of the cheapest walk from any of a set of source tiles, for every tile of the map
+pub struct DijkstraMap {
+    dist: Vec<f32>,
+}
+
+impl DijkstraMap {
+    /// Distances from the nearest of `sources`
+    pub fn new(map: &Map, blocked: &HashSet<usize>, sources: &[usize]) -> Self {
+        let mut dist = vec![f32::INFINITY; map.tiles.len()];
+        for &src in sources {
+            dist[src] = 0.;
+        }
+        Self::relax(map, blocked, &mut dist);
+
+        DijkstraMap { dist }
+    }
+
+    /// Map whose downhill direction leads away from `sources`
+    pub fn flee(map: &Map, blocked: &HashSet<usize>, sources: &[usize]) -> Self {
+        let mut dist = Self::new(map, blocked, sources).dist;
+        for d in dist.iter_mut().filter(|d| d.is_finite()) {
+            *d *= FLEE_FACTOR;
+        }
+        Self::relax(map, blocked, &mut dist);
+
+        DijkstraMap { dist }
+    }
+
+    /// Propagate the finite values of `dist` until every tile holds its cheapest cost
+    fn relax(map: &Map, blocked: &HashSet<usize>, dist: &mut [f32]) {
+        let mut open: BinaryHeap<Node> = dist
+            .iter()
+            .enumerate()
+            .filter(|(_, d)| d.is_finite())
+            .map(|(idx, &cost)| Node { cost, idx })
+            .collect();
+
+        while let Some(Node { cost, idx }) = open.pop() {
+            if cost > dist[idx] {
+                continue;
+            }
+            for (next, step) in exits(map, blocked, idx, None) {
+                let new_cost = cost + step;
+                if new_cost < dist[next] {
+                    dist[next] = new_cost;
+                    open.push(Node {
+                        cost: new_cost,
+                        idx: next,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Value of the map at `idx` (`None` if unreachable)
+    pub fn get(&self, idx: usize) -> Option<f32> {
+        Some(self.dist[idx]).filter(|d| d.is_finite())
+    }
+
+    /// Best neighbour to step on when going downhill from `from`
+    pub fn next_step(&self, map: &Map, blocked: &HashSet<usize>, from: usize) -> Option<usize> {
+        exits(map, blocked, from, None)
+            .filter_map(|(next, _)| self.get(next).map(|d| (next, d)))
+            .filter(|&(_, d)| self.get(from).map_or(true, |here| d < here))
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
+            .map(|(next, _)| next)
+    }
+}
+
+/// Finds paths, caching them (and Dijkstra maps) until the map or the blocked tiles change
+#[derive(Default)]
+pub struct Pathfinder {
+    revision: Option<u64>,
+    blocked: HashSet<usize>,
+    paths: HashMap<(usize, usize), Option<Vec<usize>>>,
+    towards: HashMap<Vec<usize>, DijkstraMap>,
+    away: HashMap<Vec<usize>, DijkstraMap>,
+}
+
+impl Pathfinder {
+    /// Sets the tiles to avoid (dropping the cache if they or the map changed since last time)
+    pub fn update(&mut self, map: &Map, blocked: HashSet<usize>) {
+        if blocked != self.blocked {
+            self.blocked = blocked;
+            self.forget();
+        }
+        self.forget_stale(map);
+    }
+
+    /// Drops the cache if `map` changed since it was filled
+    fn forget_stale(&mut self, map: &Map) {
+        if self.revision != Some(map.revision) {
+            self.revision = Some(map.revision);
+            self.forget();
+        }
+    }
+
+    /// Drops every cached path and map
+    fn forget(&mut self) {
+        self.paths.clear();
+        self.towards.clear();
+        self.away.clear();
+    }
+
+    /// Shortest path between two points (excluding `start`, including `end`)
+    pub fn path(&mut self, map: &Map, start: Point, end: Point) -> Option<Vec<Point>> {
+        self.forget_stale(map);
+        let (start, end) = (map.xy_idx(start.x, start.y), map.xy_idx(end.x, end.y));
+        let blocked = &self.blocked;
+        self.paths
+            .entry((start, end))
+            .or_insert_with(|| a_star(map, blocked, start, end))
+            .as_ref()
+            .map(|path| path.iter().map(|&idx| map.idx_xy(idx)).collect())
+    }
+
+    /// Next step from `from` towards the nearest of `sources` (e.g. the player or the stairs)
+    pub fn step_towards(&mut self, map: &Map, sources: &[Point], from: Point) -> Option<Point> {
+        self.forget_stale(map);
+        let blocked = &self.blocked;
+        Self::cached(&mut self.towards, Self::key(map, sources), |key| {
+            DijkstraMap::new(map, blocked, key)
+        })
+        .next_step(map, blocked, map.xy_idx(from.x, from.y))
+        .map(|idx| map.idx_xy(idx))
+    }
+
+    /// Next step from `from` when running away from `sources`
+    pub fn step_away(&mut self, map: &Map, sources: &[Point], from: Point) -> Option<Point> {
+        self.forget_stale(map);
+        let blocked = &self.blocked;
+        Self::cached(&mut self.away, Self::key(map, sources), |key| {
+            DijkstraMap::flee(map, blocked, key)
+        })
+        .next_step(map, blocked, map.xy_idx(from.x, from.y))
+        .map(|idx| map.idx_xy(idx))
+    }
+
+    /// Map cached for `key`, built by `build` if missing
+    fn cached(
+        cache: &mut HashMap<Vec<usize>, DijkstraMap>,
+        key: Vec<usize>,
+        build: impl FnOnce(&[usize]) -> DijkstraMap,
+    ) -> &DijkstraMap {
+        match cache.entry(key) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let dijkstra = build(entry.key());
+                entry.insert(dijkstra)
+            }
+        }
+    }
+
+    /// Cache key for a set of points
+    fn key(map: &Map, points: &[Point]) -> Vec<usize> {
+        let mut key: Vec<usize> = points.iter().map(|p| map.xy_idx(p.x, p.y)).collect();
+        key.sort();
+        key.dedup();
+        key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::base::Tile;
+
+    /// Open `width`x`height` room surrounded by walls
+    fn walled_room(width: usize, height: usize) -> Map {
+        let mut map = Map::all(width, height, Tile::Wall);
+        for y in 1..height - 1 {
+            for x in 1..width - 1 {
+                *map.at_mut(x, y) = Tile::Floor;
+            }
+        }
+        map
+    }
+
+    #[test]
+    fn a_star_goes_around_walls() {
+        // Vertical wall with a single gap at the bottom.
+        let mut map = walled_room(7, 7);
+        for y in 1..4 {
+            *map.at_mut(3, y) = Tile::Wall;
+        }
+        let (start, end) = (map.xy_idx(1, 1), map.xy_idx(5, 1));
+
+        let path = a_star(&map, &HashSet::new(), start, end).expect("No path found");
+
+        assert_eq!(path.last(), Some(&end));
+        assert!(path.iter().all(|&idx| map.tiles[idx].is_passable()));
+        assert!(path.contains(&map.xy_idx(3, 4)) || path.contains(&map.xy_idx(3, 5)));
+    }
+
+    #[test]
+    fn a_star_fails_when_walled_off() {
+        let mut map = walled_room(7, 7);
+        for y in 1..6 {
+            *map.at_mut(3, y) = Tile::Wall;
+        }
+
+        let path = a_star(&map, &HashSet::new(), map.xy_idx(1, 1), map.xy_idx(5, 1));
+
+        assert!(path.is_none());
+    }
+
+    #[test]
+    fn dijkstra_leads_to_nearest_source() {
+        let map = walled_room(9, 3);
+        let sources = [map.xy_idx(1, 1), map.xy_idx(7, 1)];
+        let dijkstra = DijkstraMap::new(&map, &HashSet::new(), &sources);
+
+        assert_eq!(dijkstra.get(sources[0]), Some(0.));
+        assert_eq!(dijkstra.get(map.xy_idx(0, 0)), None);
+        assert_eq!(
+            dijkstra.next_step(&map, &HashSet::new(), map.xy_idx(5, 1)),
+            Some(map.xy_idx(6, 1))
+        );
+    }
+
+    #[test]
+    fn fleeing_increases_distance() {
+        let map = walled_room(9, 3);
+        let threat = map.xy_idx(3, 1);
+        let flee = DijkstraMap::flee(&map, &HashSet::new(), &[threat]);
+
+        let step = flee
+            .next_step(&map, &HashSet::new(), map.xy_idx(4, 1))
+            .expect("Nowhere to flee");
+
+        assert_eq!(map.idx_xy(step).x, 5);
+    }
+
+    #[test]
+    fn cache_is_dropped_when_map_changes() {
+        let mut map = walled_room(7, 3);
+        let mut pathfinder = Pathfinder::default();
+        let (from, goal) = (Point::new(1, 1), [Point::new(5, 1)]);
+
+        pathfinder.update(&map, HashSet::new());
+        assert_eq!(
+            pathfinder.step_towards(&map, &goal, from),
+            Some(Point::new(2, 1))
+        );
+
+        *map.at_mut(3, 1) = Tile::Wall;
+        assert_eq!(pathfinder.step_towards(&map, &goal, from), None);
+    }
+
+    #[test]
+    fn blocked_tiles_are_stepped_around() {
+        let map = walled_room(7, 4);
+        let mut pathfinder = Pathfinder::default();
+        let (from, goal) = (Point::new(1, 1), [Point::new(5, 1)]);
+
+        pathfinder.update(&map, HashSet::new());
+        assert_eq!(
+            pathfinder.step_towards(&map, &goal, from),
+            Some(Point::new(2, 1))
+        );
+
+        pathfinder.update(&map, vec![map.xy_idx(2, 1)].into_iter().collect());
+        assert_eq!(
+            pathfinder.step_towards(&map, &goal, from),
+            Some(Point::new(2, 2))
+        );
+        assert!(pathfinder.path(&map, from, goal[0]).is_some());
+    }
+
+    #[test]
+    fn occupied_corridors_are_routed_around() {
+        // Two ways around a wall: the top one is taken by someone.
+        let mut map = walled_room(7, 5);
+        *map.at_mut(3, 2) = Tile::Wall;
+        let mut pathfinder = Pathfinder::default();
+        let (from, goal) = (Point::new(2, 1), Point::new(4, 1));
+
+        pathfinder.update(&map, vec![map.xy_idx(3, 1)].into_iter().collect());
+
+        let step = pathfinder
+            .step_towards(&map, &[goal], from)
+            .expect("No way around");
+        assert_eq!(step.y, 2);
+        let path = pathfinder.path(&map, from, goal).expect("No path found");
+        assert!(!path.contains(&Point::new(3, 1)));
+    }
+
+    #[test]
+    fn cached_paths_follow_blocked_tiles() {
+        let map = walled_room(7, 3);
+        let mut pathfinder = Pathfinder::default();
+        let (from, goal) = (Point::new(1, 1), Point::new(5, 1));
+
+        pathfinder.update(&map, HashSet::new());
+        assert!(pathfinder.path(&map, from, goal).is_some());
+
+        pathfinder.update(&map, vec![map.xy_idx(3, 1)].into_iter().collect());
+        assert_eq!(pathfinder.path(&map, from, goal), None);
+    }
+
+    #[test]
+    fn maps_for_different_sources_are_kept() {
+        let map = walled_room(9, 3);
+        let mut pathfinder = Pathfinder::default();
+        let from = Point::new(4, 1);
+
+        pathfinder.update(&map, HashSet::new());
+        let left = pathfinder.step_towards(&map, &[Point::new(1, 1)], from);
+        let right = pathfinder.step_towards(&map, &[Point::new(7, 1)], from);
+
+        assert_eq!(left, Some(Point::new(3, 1)));
+        assert_eq!(right, Some(Point::new(5, 1)));
+        assert_eq!(pathfinder.towards.len(), 2);
+    }
+}
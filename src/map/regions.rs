@@ -0,0 +1,257 @@
+use crate::map::base::{Map, Tile};
+use crate::utils::dir::Dir;
+use crate::utils::rect::Rect;
+use rltk::Point;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet, VecDeque};
+
+/// Part of the map a tile belongs to (ids are indices, one sequence per kind of region)
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub enum Region {
+    /// One of `Map::rooms`
+    Room(usize),
+    /// Connected stretch of open tiles between rooms
+    Corridor(usize),
+    /// Connected stretch of open tiles on maps without rooms
+    Cave(usize),
+}
+
+/// What a room is meant for
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RoomTag {
+    Start,
+    Boss,
+    Treasure,
+    Shop,
+}
+
+impl Map {
+    /// Recomputes the region of every tile and the room adjacency graph.
+    ///
+    /// Generators call this once they are done carving; room tags are kept.
+    pub fn compute_regions(&mut self) {
+        self.regions = vec![None; self.tiles.len()];
+        for (id, room) in self.rooms.iter().enumerate() {
+            for y in room.y..room.y + room.height {
+                for x in room.x..room.x + room.width {
+                    let idx = self.xy_idx(x, y);
+                    self.regions[idx] = Some(Region::Room(id));
+                }
+            }
+        }
+
+        // Whatever is open outside rooms gets split in connected areas.
+        let mut links: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); self.rooms.len()];
+        let mut area_count = 0;
+        for start in 0..self.tiles.len() {
+            if self.regions[start].is_some() || self.tiles[start] == Tile::Wall {
+                continue;
+            }
+
+            let region = if self.rooms.is_empty() {
+                Region::Cave(area_count)
+            } else {
+                Region::Corridor(area_count)
+            };
+            area_count += 1;
+
+            // Every room touched by the area is linked to all the others.
+            let touched = self.flood_fill(start, region);
+            for &a in &touched {
+                links[a].extend(touched.iter().filter(|&&b| b != a));
+            }
+        }
+
+        // Rooms can also touch each other directly.
+        for idx in 0..self.tiles.len() {
+            if let Some(Region::Room(a)) = self.regions[idx] {
+                for b in self.neighbour_rooms(idx) {
+                    if a != b {
+                        links[a].insert(b);
+                    }
+                }
+            }
+        }
+
+        self.room_links = links
+            .into_iter()
+            .map(|set| set.into_iter().collect())
+            .collect();
+    }
+
+    /// Marks the open area containing `start` as `region`, returning the rooms it touches
+    fn flood_fill(&mut self, start: usize, region: Region) -> HashSet<usize> {
+        let mut touched = HashSet::new();
+        let mut queue = VecDeque::new();
+        self.regions[start] = Some(region);
+        queue.push_back(start);
+
+        while let Some(idx) = queue.pop_front() {
+            touched.extend(self.neighbour_rooms(idx));
+
+            let p = self.idx_xy(idx);
+            for dir in Dir::cardinals() {
+                let (dx, dy) = dir.delta();
+                let (x, y) = (p.x + dx, p.y + dy);
+                if !self.contains_point(x, y) {
+                    continue;
+                }
+                let next = self.xy_idx(x, y);
+                if self.regions[next].is_none() && self.tiles[next] != Tile::Wall {
+                    self.regions[next] = Some(region);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        touched
+    }
+
+    /// Rooms the tiles next to `idx` (cardinals only) belong to
+    fn neighbour_rooms(&self, idx: usize) -> Vec<usize> {
+        let p = self.idx_xy(idx);
+        Dir::cardinals()
+            .map(Dir::delta)
+            .map(|(dx, dy)| (p.x + dx, p.y + dy))
+            .filter(|&(x, y)| self.contains_point(x, y))
+            .filter_map(|(x, y)| match self.regions[self.xy_idx(x, y)] {
+                Some(Region::Room(id)) => Some(id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Region the tile at `p` belongs to (`None` for walls and outside the map)
+    pub fn region_at(&self, p: Point) -> Option<Region> {
+        if !self.contains_point(p.x, p.y) {
+            return None;
+        }
+
+        self.regions[self.xy_idx(p.x, p.y)]
+    }
+
+    /// Room containing `p` (if any)
+    pub fn room_at(&self, p: Point) -> Option<usize> {
+        match self.region_at(p)? {
+            Region::Room(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Rooms directly reachable from `room` (through a corridor or a shared edge)
+    pub fn rooms_adjacent_to(&self, room: usize) -> &[usize] {
+        self.room_links.get(room).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Gives `tag` to `room`
+    pub fn tag_room(&mut self, room: usize, tag: RoomTag) {
+        self.room_tags.entry(room).or_default().insert(tag);
+    }
+
+    /// Checks if `room` has been given `tag`
+    pub fn room_has_tag(&self, room: usize, tag: RoomTag) -> bool {
+        self.room_tags
+            .get(&room)
+            .map_or(false, |tags| tags.contains(&tag))
+    }
+
+    /// Rooms that have been given `tag`
+    pub fn rooms_tagged(&self, tag: RoomTag) -> Vec<usize> {
+        let mut rooms: Vec<usize> = self
+            .room_tags
+            .iter()
+            .filter(|(_, tags)| tags.contains(&tag))
+            .map(|(&room, _)| room)
+            .collect();
+        rooms.sort();
+        rooms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::base::Room;
+
+    /// Two rooms joined by a corridor, plus a third room touching the second one
+    ///
+    /// ```text
+    /// ##############
+    /// #...#####....#
+    /// #...-----....#
+    /// #...#####....#
+    /// #########....#
+    /// ##############
+    /// ```
+    fn test_map() -> Map {
+        let mut map = Map::all(14, 6, Tile::Wall);
+        map.add_room(Room {
+            x: 1,
+            y: 1,
+            width: 3,
+            height: 3,
+        });
+        map.add_room(Room {
+            x: 9,
+            y: 1,
+            width: 4,
+            height: 2,
+        });
+        map.add_room(Room {
+            x: 9,
+            y: 3,
+            width: 4,
+            height: 2,
+        });
+        for x in 4..9 {
+            *map.at_mut(x, 2) = Tile::Floor;
+        }
+        map.compute_regions();
+        map
+    }
+
+    #[test]
+    fn tiles_know_their_region() {
+        let map = test_map();
+
+        assert_eq!(map.room_at(Point::new(2, 2)), Some(0));
+        assert_eq!(map.room_at(Point::new(10, 4)), Some(2));
+        assert_eq!(map.region_at(Point::new(6, 2)), Some(Region::Corridor(0)));
+        assert_eq!(map.region_at(Point::new(0, 0)), None);
+        assert_eq!(map.room_at(Point::new(6, 2)), None);
+    }
+
+    #[test]
+    fn rooms_are_linked_by_corridors_and_edges() {
+        let map = test_map();
+
+        assert_eq!(map.rooms_adjacent_to(0), &[1]);
+        assert_eq!(map.rooms_adjacent_to(1), &[0, 2]);
+        assert_eq!(map.rooms_adjacent_to(2), &[1]);
+        assert!(map.rooms_adjacent_to(42).is_empty());
+    }
+
+    #[test]
+    fn maps_without_rooms_have_caves() {
+        let mut map = Map::all(5, 3, Tile::Wall);
+        *map.at_mut(1, 1) = Tile::Floor;
+        *map.at_mut(3, 1) = Tile::Floor;
+        map.compute_regions();
+
+        assert_eq!(map.region_at(Point::new(1, 1)), Some(Region::Cave(0)));
+        assert_eq!(map.region_at(Point::new(3, 1)), Some(Region::Cave(1)));
+    }
+
+    #[test]
+    fn rooms_can_be_tagged() {
+        let mut map = test_map();
+        map.tag_room(1, RoomTag::Boss);
+        map.tag_room(2, RoomTag::Treasure);
+        map.tag_room(0, RoomTag::Treasure);
+
+        assert!(map.room_has_tag(1, RoomTag::Boss));
+        assert!(!map.room_has_tag(0, RoomTag::Boss));
+        assert_eq!(map.rooms_tagged(RoomTag::Treasure), vec![0, 2]);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::map::base::Map;
+use crate::utils::dir::Dir;
 use rltk::{Algorithm2D, BaseMap, Point};
 
 /// Needed for interoperability with rltk
@@ -11,29 +12,19 @@ impl Algorithm2D for Map {
 /// Needed for interoperability with rltk
 impl BaseMap for Map {
     fn is_opaque(&self, idx: usize) -> bool {
-        !self.tiles[idx as usize].is_passable()
+        self.tiles[idx].is_opaque()
     }
 
     fn get_available_exits(&self, idx: usize) -> Vec<(usize, f32)> {
-        let Point { x, y } = self.idx_xy(idx);
+        let from = self.idx_xy(idx);
 
-        vec![
-            // Cardinals.
-            (1, 0),
-            (0, 1),
-            (-1, 0),
-            (0, -1),
-            // Diagonals.
-            (1, 1),
-            (1, -1),
-            (-1, 1),
-            (-1, -1),
-        ]
-        .into_iter()
-        .map(|(dx, dy)| (x + dx, y + dy))
-        // Diagonals cost sqrt(2) to walk through.
-        .map(|(x, y)| (self.xy_idx(x, y), if x == 0 || y == 0 { 1. } else { 1.42 }))
-        .filter(|&(pos, _)| self.tiles[pos].is_passable())
-        .collect()
+        // Movement rules take care of bounds, diagonals and tile costs.
+        Dir::all()
+            .filter_map(|dir| {
+                let cost = self.step_cost(from, dir)?;
+                let (dx, dy) = dir.delta();
+                Some((self.xy_idx(from.x + dx, from.y + dy), cost))
+            })
+            .collect()
     }
 }
@@ -0,0 +1,256 @@
+use crate::map::base::Map;
+use crate::map::pathfinding::DijkstraMap;
+use crate::utils::dir::Dir;
+use rltk::Point;
+use serde::Serialize;
+use std::collections::{HashSet, VecDeque};
+
+/// Numbers describing the shape of a generated map
+#[derive(Serialize, Clone, Debug)]
+pub struct MapStats {
+    pub width: usize,
+    pub height: usize,
+    pub rooms: usize,
+    /// Share of the tiles that can be walked on (doors included)
+    pub floor_ratio: f32,
+    /// Number of walkable areas not connected to each other
+    pub areas: usize,
+    /// Number of tiles in the largest walkable area
+    pub largest_area: usize,
+    /// Share of the walkable tiles belonging to the largest area
+    pub largest_area_ratio: f32,
+    /// Walkable tiles outside rooms with a single way out
+    pub dead_ends: usize,
+    /// Average cost of walking between the centers of two rooms (`None` without two connected
+    /// rooms)
+    pub mean_room_distance: Option<f32>,
+}
+
+impl MapStats {
+    /// Compute the stats of `map`
+    pub fn of(map: &Map) -> Self {
+        let areas = area_sizes(map);
+        let walkable: usize = areas.iter().sum();
+        let largest = areas.iter().copied().max().unwrap_or(0);
+
+        MapStats {
+            width: map.width,
+            height: map.height,
+            rooms: map.rooms.len(),
+            floor_ratio: ratio(walkable, map.tiles.len()),
+            areas: areas.len(),
+            largest_area: largest,
+            largest_area_ratio: ratio(largest, walkable),
+            dead_ends: dead_ends(map),
+            mean_room_distance: mean_room_distance(map),
+        }
+    }
+}
+
+/// Summary of the values a stat took over several maps
+#[derive(Serialize, Clone, Debug)]
+pub struct Distribution {
+    pub min: f32,
+    pub mean: f32,
+    pub median: f32,
+    pub max: f32,
+}
+
+impl Distribution {
+    /// Summarize `values` (`None` if there are none)
+    pub fn of(values: &[f32]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+
+        let mut sorted = values.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).expect("Stats can't be NaN"));
+        let mid = sorted.len() / 2;
+        let median = if sorted.len() % 2 == 0 {
+            (sorted[mid - 1] + sorted[mid]) / 2.
+        } else {
+            sorted[mid]
+        };
+
+        Some(Distribution {
+            min: sorted[0],
+            mean: sorted.iter().sum::<f32>() / sorted.len() as f32,
+            median,
+            max: sorted[sorted.len() - 1],
+        })
+    }
+}
+
+/// `part / total` (0 if `total` is 0)
+fn ratio(part: usize, total: usize) -> f32 {
+    if total == 0 {
+        0.
+    } else {
+        part as f32 / total as f32
+    }
+}
+
+/// Checks if the tile at `idx` can be walked on (according to the map movement rules)
+fn is_walkable(map: &Map, idx: usize) -> bool {
+    map.movement.tile_cost(map.tiles[idx]).is_some()
+}
+
+/// Number of walkable tiles outside rooms that can be left in a single direction
+fn dead_ends(map: &Map) -> usize {
+    (0..map.tiles.len())
+        .filter(|&idx| is_walkable(map, idx))
+        .map(|idx| map.idx_xy(idx))
+        .filter(|&p| map.room_at(p).is_none())
+        .filter(|&p| exit_count(map, p) == 1)
+        .count()
+}
+
+/// Number of directions one can walk towards from `p`
+fn exit_count(map: &Map, p: Point) -> usize {
+    Dir::all()
+        .filter(|&d| map.step_cost(p, d).is_some())
+        .count()
+}
+
+/// Average cost of walking between each couple of connected rooms (from center to center)
+fn mean_room_distance(map: &Map) -> Option<f32> {
+    let centers: Vec<usize> = map
+        .rooms
+        .iter()
+        .map(|room| map.xy_idx(room.x + room.width / 2, room.y + room.height / 2))
+        .collect();
+
+    let blocked = HashSet::new();
+    let distances: Vec<f32> = centers
+        .iter()
+        .enumerate()
+        .flat_map(|(i, &from)| {
+            let dijkstra = DijkstraMap::new(map, &blocked, &[from]);
+            centers[i + 1..]
+                .iter()
+                .filter_map(|&to| dijkstra.get(to))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    Distribution::of(&distances).map(|d| d.mean)
+}
+
+/// Sizes of the walkable areas of `map` (following its movement rules)
+pub fn area_sizes(map: &Map) -> Vec<usize> {
+    let mut visited = vec![false; map.tiles.len()];
+    let mut sizes = vec![];
+
+    for start in 0..map.tiles.len() {
+        if visited[start] || !is_walkable(map, start) {
+            continue;
+        }
+
+        let mut size = 0;
+        let mut queue = VecDeque::new();
+        visited[start] = true;
+        queue.push_back(start);
+        while let Some(idx) = queue.pop_front() {
+            size += 1;
+            let p = map.idx_xy(idx);
+            for dir in Dir::all() {
+                if map.step_cost(p, dir).is_none() {
+                    continue;
+                }
+                let (dx, dy) = dir.delta();
+                let next = map.xy_idx(p.x + dx, p.y + dy);
+                if !visited[next] {
+                    visited[next] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+        sizes.push(size);
+    }
+
+    sizes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::base::{Room, Tile};
+
+    #[test]
+    fn walls_split_areas() {
+        // ..#..
+        // ..#..
+        let mut map = Map::empty(5, 2);
+        *map.at_mut(2, 0) = Tile::Wall;
+        *map.at_mut(2, 1) = Tile::Wall;
+
+        let stats = MapStats::of(&map);
+
+        assert_eq!(area_sizes(&map), vec![4, 4]);
+        assert_eq!(stats.areas, 2);
+        assert!((stats.floor_ratio - 0.8).abs() < f32::EPSILON);
+        assert!((stats.largest_area_ratio - 0.5).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn doors_connect_areas() {
+        let mut map = Map::empty(5, 1);
+        *map.at_mut(2, 0) = Tile::ClosedDoor;
+
+        assert_eq!(area_sizes(&map), vec![5]);
+    }
+
+    #[test]
+    fn corridor_ends_are_dead_ends() {
+        // #####
+        // #...#
+        // #####
+        let mut map = Map::all(5, 3, Tile::Wall);
+        for x in 1..4 {
+            *map.at_mut(x, 1) = Tile::Floor;
+        }
+        map.compute_regions();
+
+        assert_eq!(MapStats::of(&map).dead_ends, 2);
+    }
+
+    #[test]
+    fn rooms_are_measured_center_to_center() {
+        let mut map = Map::all(12, 5, Tile::Wall);
+        for &x in &[1, 8] {
+            map.add_room(Room {
+                x,
+                y: 1,
+                width: 3,
+                height: 3,
+            });
+        }
+        for x in 4..8 {
+            *map.at_mut(x, 2) = Tile::Floor;
+        }
+        map.compute_regions();
+
+        let distance = MapStats::of(&map).mean_room_distance.unwrap();
+        assert!((distance - 7.).abs() < f32::EPSILON);
+        assert_eq!(MapStats::of(&Map::empty(3, 3)).mean_room_distance, None);
+    }
+
+    #[test]
+    fn distributions_have_median() {
+        let dist = Distribution::of(&[4., 1., 3., 2.]).unwrap();
+
+        assert!((dist.min - 1.).abs() < f32::EPSILON);
+        assert!((dist.median - 2.5).abs() < f32::EPSILON);
+        assert!((dist.mean - 2.5).abs() < f32::EPSILON);
+        assert!((dist.max - 4.).abs() < f32::EPSILON);
+        assert!(Distribution::of(&[]).is_none());
+    }
+
+    #[test]
+    fn walled_maps_have_no_areas() {
+        let stats = MapStats::of(&Map::all(3, 3, Tile::Wall));
+
+        assert_eq!(stats.areas, 0);
+        assert!(stats.largest_area_ratio.abs() < f32::EPSILON);
+    }
+}
@@ -0,0 +1,207 @@
+use crate::map::base::{Map, Room, Tile};
+use crate::map::regions::RoomTag;
+use log::info;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Percent chance for a room (other than the start one) to get a theme
+const THEME_CHANCE: f32 = 30.;
+
+/// Percent of the floor of flooded rooms covered in water
+const FLOODED_SHARE: f32 = 60.;
+
+/// Percent of the floor of overgrown rooms covered in tall grass
+const OVERGROWN_SHARE: f32 = 50.;
+
+/// Libraries need some room for their shelves
+const MIN_LIBRARY_SIZE: i32 = 5;
+
+/// Flavour of a room, changing its tiles and what spawns in it
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Copy, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Theme {
+    Library,
+    Flooded,
+    Overgrown,
+}
+
+impl Theme {
+    /// All the themes
+    pub const ALL: [Theme; 3] = [Theme::Library, Theme::Flooded, Theme::Overgrown];
+
+    /// Checks if the theme fits `room`
+    fn fits(self, room: &Room) -> bool {
+        match self {
+            Theme::Library => room.width >= MIN_LIBRARY_SIZE && room.height >= MIN_LIBRARY_SIZE,
+            Theme::Flooded | Theme::Overgrown => true,
+        }
+    }
+}
+
+/// Picks the start, boss (i.e. exit) and treasure rooms and gives some of the others a theme.
+///
+/// Does nothing on maps without rooms.
+pub fn apply<R: Rng + ?Sized>(map: &mut Map, rng: &mut R) {
+    if map.rooms.is_empty() {
+        return;
+    }
+    let start = rng.gen_range(0, map.rooms.len());
+    tag_rooms(map, start);
+
+    for room in 0..map.rooms.len() {
+        if room == start || rng.gen_range(0., 100.) >= THEME_CHANCE {
+            continue;
+        }
+        let fitting: Vec<Theme> = Theme::ALL
+            .iter()
+            .copied()
+            .filter(|theme| theme.fits(&map.rooms[room]))
+            .collect();
+        if let Some(&theme) = fitting.choose(rng) {
+            info!("Room {} gets theme {:?}.", room, theme);
+            apply_theme(map, room, theme, rng);
+        }
+    }
+}
+
+/// Tags `start` as the start room, the room farthest from it as the boss room and dead ends as
+/// treasure rooms
+fn tag_rooms(map: &mut Map, start: usize) {
+    map.tag_room(start, RoomTag::Start);
+
+    // Breadth first visit of the room graph.
+    let mut distance = vec![None; map.rooms.len()];
+    let mut queue = VecDeque::new();
+    distance[start] = Some(0);
+    queue.push_back(start);
+    while let Some(room) = queue.pop_front() {
+        for &next in map.rooms_adjacent_to(room) {
+            if distance[next].is_none() {
+                distance[next] = distance[room].map(|d| d + 1);
+                queue.push_back(next);
+            }
+        }
+    }
+
+    let boss = (0..map.rooms.len())
+        .filter_map(|room| distance[room].map(|d| (room, d)))
+        .filter(|&(_, d)| d > 0)
+        .max_by_key(|&(_, d)| d)
+        .map(|(room, _)| room);
+    if let Some(boss) = boss {
+        map.tag_room(boss, RoomTag::Boss);
+    }
+
+    for room in 0..map.rooms.len() {
+        if room != start && Some(room) != boss && map.rooms_adjacent_to(room).len() == 1 {
+            map.tag_room(room, RoomTag::Treasure);
+        }
+    }
+}
+
+/// Changes the tiles of `room` to match `theme`
+fn apply_theme<R: Rng + ?Sized>(map: &mut Map, room: usize, theme: Theme, rng: &mut R) {
+    map.room_themes.insert(room, theme);
+
+    let (x0, y0, w, h) = {
+        let room = &map.rooms[room];
+        (room.x, room.y, room.width, room.height)
+    };
+    for y in y0..y0 + h {
+        for x in x0..x0 + w {
+            if map.at(x, y) != Tile::Floor {
+                continue;
+            }
+
+            let on_edge = x == x0 || y == y0 || x == x0 + w - 1 || y == y0 + h - 1;
+            let new_tile = match theme {
+                // Shelves line the walls, leaving entrances free.
+                Theme::Library if on_edge && !next_to_entrance(map, x, y) => Tile::Bookshelf,
+                Theme::Library => continue,
+                Theme::Flooded if rng.gen_range(0., 100.) < FLOODED_SHARE => Tile::ShallowWater,
+                Theme::Overgrown if rng.gen_range(0., 100.) < OVERGROWN_SHARE => Tile::TallGrass,
+                Theme::Flooded | Theme::Overgrown => continue,
+            };
+            *map.at_mut(x, y) = new_tile;
+        }
+    }
+}
+
+/// Checks if any tile around (`x`, `y`) leads out of the room (i.e. isn't a wall or part of it)
+fn next_to_entrance(map: &Map, x: i32, y: i32) -> bool {
+    let room = map.room_at(rltk::Point::new(x, y));
+    (-1..=1)
+        .flat_map(|dy| (-1..=1).map(move |dx| (x + dx, y + dy)))
+        .any(|(nx, ny)| {
+            let p = rltk::Point::new(nx, ny);
+            map.at(nx, ny) != Tile::Wall && map.room_at(p) != room
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// Chain of three rooms (0 - 1 - 2) plus a dead end hanging off the middle one (3)
+    fn test_map() -> Map {
+        let mut map = Map::all(30, 14, Tile::Wall);
+        for &(x, y) in &[(1, 1), (10, 1), (19, 1), (10, 8)] {
+            map.add_room(Room {
+                x,
+                y,
+                width: 6,
+                height: 5,
+            });
+        }
+        // Horizontal corridors, then a vertical one.
+        for x in (7..10).chain(16..19) {
+            *map.at_mut(x, 3) = Tile::Floor;
+        }
+        for y in 6..8 {
+            *map.at_mut(12, y) = Tile::Floor;
+        }
+        map.compute_regions();
+        map
+    }
+
+    #[test]
+    fn farthest_room_is_boss_and_dead_ends_are_treasure() {
+        let mut map = test_map();
+        tag_rooms(&mut map, 0);
+
+        assert_eq!(map.rooms_tagged(RoomTag::Start), vec![0]);
+        assert_eq!(map.rooms_tagged(RoomTag::Boss).len(), 1);
+        assert!(map.room_has_tag(2, RoomTag::Boss) || map.room_has_tag(3, RoomTag::Boss));
+        // Whichever dead end isn't the boss room holds the treasure.
+        assert_eq!(map.rooms_tagged(RoomTag::Treasure).len(), 1);
+        assert!(!map.room_has_tag(1, RoomTag::Treasure));
+    }
+
+    #[test]
+    fn libraries_keep_entrances_free() {
+        let mut map = test_map();
+        let mut rng = StdRng::seed_from_u64(42);
+        apply_theme(&mut map, 1, Theme::Library, &mut rng);
+
+        assert_eq!(map.room_themes.get(&1), Some(&Theme::Library));
+        assert_eq!(map.at(11, 1), Tile::Bookshelf);
+        // Corridors enter from the west, east and south.
+        assert!(map.at(10, 3).is_passable());
+        assert!(map.at(15, 3).is_passable());
+        assert!(map.at(12, 5).is_passable());
+    }
+
+    #[test]
+    fn flooded_rooms_get_water() {
+        let mut map = test_map();
+        let mut rng = StdRng::seed_from_u64(42);
+        apply_theme(&mut map, 0, Theme::Flooded, &mut rng);
+
+        assert!(map.tiles.contains(&Tile::ShallowWater));
+        assert!(map.tiles.iter().all(|&t| t != Tile::TallGrass));
+    }
+}
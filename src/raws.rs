@@ -0,0 +1,338 @@
+use crate::components::EquipmentSlot;
+use crate::map::theming::Theme;
+use crate::utils::random_table::RandomTable;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+
+/// How an entity looks
+#[derive(Deserialize, Clone, Debug)]
+pub struct RawRenderable {
+    /// A single character
+    pub glyph: char,
+    /// Html colour (e.g. `#FF0000`)
+    pub fg: String,
+    /// Html colour (e.g. `#000000`)
+    pub bg: String,
+}
+
+/// Light carried by an entity
+#[derive(Deserialize, Clone, Debug)]
+pub struct RawLight {
+    pub radius: i32,
+    /// Html colour (e.g. `#FFCC66`)
+    pub colour: String,
+}
+
+/// Fighting statistics of an entity
+#[derive(Deserialize, Clone, Debug)]
+pub struct RawStats {
+    pub max_hp: i32,
+    pub defense: i32,
+    pub power: i32,
+}
+
+/// What happens when an item is worn
+#[derive(Deserialize, Clone, Debug)]
+pub struct RawEquippable {
+    pub slot: EquipmentSlot,
+    pub power_bonus: Option<i32>,
+    pub defense_bonus: Option<i32>,
+}
+
+/// A status lasting `turns` turns, doing `amount` of something each turn
+#[derive(Deserialize, Clone, Debug)]
+pub struct RawOverTime {
+    pub turns: i32,
+    pub amount: i32,
+}
+
+/// What an item does
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct RawItem {
+    pub consumable: bool,
+    pub range: Option<i32>,
+    pub area_of_effect: Option<i32>,
+    pub healing: Option<i32>,
+    pub damage: Option<i32>,
+    pub confusion: Option<i32>,
+    pub blindness: Option<i32>,
+    pub poison: Option<RawOverTime>,
+    pub regeneration: Option<RawOverTime>,
+    pub food: bool,
+    pub teleports: bool,
+    /// Unlocks a locked door
+    pub key: bool,
+    pub equippable: Option<RawEquippable>,
+}
+
+/// How a trap behaves (its effects are described like the ones of items)
+#[derive(Deserialize, Clone, Debug)]
+pub struct RawTrap {
+    /// Traps disappear after firing once unless they are reusable
+    #[serde(default)]
+    pub single_use: bool,
+    #[serde(flatten)]
+    pub effects: RawItem,
+}
+
+/// Blueprint of an entity (each field corresponds to one or more components)
+#[derive(Deserialize, Clone, Debug)]
+pub struct Template {
+    pub name: String,
+    pub renderable: Option<RawRenderable>,
+    pub viewshed: Option<i32>,
+    pub light: Option<RawLight>,
+    pub stats: Option<RawStats>,
+    /// Energy gained per clock tick (for the player and mobs)
+    pub speed: Option<i32>,
+    pub item: Option<RawItem>,
+    pub trap: Option<RawTrap>,
+    /// Path of dialogue file (relative to `assets/`)
+    pub dialogue: Option<String>,
+}
+
+/// How likely it is for a template to be spawned at a certain dungeon depth
+#[derive(Deserialize, Clone, Debug)]
+pub struct SpawnEntry {
+    /// Template name
+    pub name: String,
+    /// Weight at `min_depth`
+    pub weight: i32,
+    pub min_depth: i32,
+    pub max_depth: i32,
+    /// How much the weight grows (or shrinks) for each level past `min_depth`
+    #[serde(default)]
+    pub add_per_depth: i32,
+    /// Room themes the entry is restricted to (none means anywhere)
+    #[serde(default)]
+    pub themes: Vec<Theme>,
+}
+
+impl SpawnEntry {
+    /// Weight of entry at `depth` (0 if the entry can't be spawned there)
+    pub fn weight_at(&self, depth: i32) -> u32 {
+        if depth < self.min_depth || depth > self.max_depth {
+            return 0;
+        }
+
+        std::cmp::max(
+            0,
+            self.weight + self.add_per_depth * (depth - self.min_depth),
+        ) as u32
+    }
+}
+
+/// Kind of spawnable thing
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum TemplateKind {
+    Player,
+    Mob,
+    Item,
+    Npc,
+    Trap,
+}
+
+/// Raw file layout
+#[derive(Deserialize)]
+struct RawFile {
+    player: Template,
+    mobs: Vec<Template>,
+    items: Vec<Template>,
+    npcs: Vec<Template>,
+    #[serde(default)]
+    traps: Vec<Template>,
+    #[serde(default)]
+    spawn_table: Vec<SpawnEntry>,
+}
+
+/// All entity templates, indexed by name
+pub struct Raws {
+    player: Template,
+    templates: BTreeMap<String, (TemplateKind, Template)>,
+    spawn_table: Vec<SpawnEntry>,
+}
+
+impl Raws {
+    /// Parse raws from their JSON representation
+    pub fn parse(src: &str) -> Result<Self, Box<dyn Error>> {
+        let raw: RawFile = serde_json::from_str(src)?;
+
+        let mut templates = BTreeMap::new();
+        let all = vec![
+            (TemplateKind::Mob, raw.mobs),
+            (TemplateKind::Item, raw.items),
+            (TemplateKind::Npc, raw.npcs),
+            (TemplateKind::Trap, raw.traps),
+        ];
+        for (kind, kind_templates) in all {
+            for template in kind_templates {
+                let name = template.name.clone();
+                if templates.insert(name.clone(), (kind, template)).is_some() {
+                    return Err(format!("Duplicate template name in raws: {}", name).into());
+                }
+            }
+        }
+
+        for entry in &raw.spawn_table {
+            if !templates.contains_key(&entry.name) {
+                return Err(format!("Unknown template in spawn table: {}", entry.name).into());
+            }
+        }
+
+        Ok(Raws {
+            player: raw.player,
+            templates,
+            spawn_table: raw.spawn_table,
+        })
+    }
+
+    /// Load raws from file
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// The player's template
+    pub fn player(&self) -> &Template {
+        &self.player
+    }
+
+    /// Template with given name (if any)
+    pub fn get(&self, name: &str) -> Option<&Template> {
+        self.templates.get(name).map(|(_, template)| template)
+    }
+
+    /// Kind of template with given name (if any)
+    pub fn kind_of(&self, name: &str) -> Option<TemplateKind> {
+        self.templates.get(name).map(|&(kind, _)| kind)
+    }
+
+    /// Table of what can be spawned at `depth` (template names), in a room with given theme
+    pub fn spawn_table(&self, depth: i32, theme: Option<Theme>) -> RandomTable<String> {
+        self.spawn_table
+            .iter()
+            .filter(|entry| {
+                entry.themes.is_empty() || theme.map_or(false, |t| entry.themes.contains(&t))
+            })
+            .fold(RandomTable::new(), |table, entry| {
+                table.add(entry.name.clone(), entry.weight_at(depth))
+            })
+    }
+
+    /// Names of all the templates of a given kind
+    pub fn names_of_kind(&self, kind: TemplateKind) -> impl Iterator<Item = &str> {
+        self.templates
+            .iter()
+            .filter(move |(_, (k, _))| *k == kind)
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_raws_are_valid() {
+        let raws = Raws::parse(include_str!("../assets/raws/spawns.json")).unwrap();
+
+        assert_eq!(raws.kind_of("Health Potion"), Some(TemplateKind::Item));
+        assert!(raws.names_of_kind(TemplateKind::Mob).count() > 0);
+        let kinds = [
+            TemplateKind::Mob,
+            TemplateKind::Item,
+            TemplateKind::Npc,
+            TemplateKind::Trap,
+        ];
+        for kind in &kinds {
+            for name in raws.names_of_kind(*kind) {
+                let renderable = raws.get(name).unwrap().renderable.as_ref().unwrap();
+                assert!(rltk::RGB::from_hex(&renderable.fg).is_ok());
+                assert!(rltk::RGB::from_hex(&renderable.bg).is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn trap_effects_are_flattened() {
+        let src = r#"{
+            "player": { "name": "Player" },
+            "mobs": [],
+            "items": [],
+            "npcs": [],
+            "traps": [{ "name": "Spikes", "trap": { "single_use": true, "damage": 5 } }]
+        }"#;
+
+        let raws = Raws::parse(src).unwrap();
+        let trap = raws.get("Spikes").unwrap().trap.as_ref().unwrap();
+
+        assert_eq!(raws.kind_of("Spikes"), Some(TemplateKind::Trap));
+        assert!(trap.single_use);
+        assert_eq!(trap.effects.damage, Some(5));
+    }
+
+    #[test]
+    fn spawn_entries_respect_depth_range() {
+        let entry = SpawnEntry {
+            name: "Orc".to_string(),
+            weight: 2,
+            min_depth: 2,
+            max_depth: 5,
+            add_per_depth: 3,
+            themes: vec![],
+        };
+
+        assert_eq!(entry.weight_at(1), 0);
+        assert_eq!(entry.weight_at(2), 2);
+        assert_eq!(entry.weight_at(4), 8);
+        assert_eq!(entry.weight_at(6), 0);
+    }
+
+    #[test]
+    fn themed_entries_only_spawn_in_themed_rooms() {
+        let src = r#"{
+            "player": { "name": "Player" },
+            "mobs": [{ "name": "Rat" }, { "name": "Leech" }],
+            "items": [],
+            "npcs": [],
+            "spawn_table": [
+                { "name": "Rat", "weight": 1, "min_depth": 1, "max_depth": 3 },
+                { "name": "Leech", "weight": 1, "min_depth": 1, "max_depth": 3, "themes": ["flooded"] }
+            ]
+        }"#;
+        let raws = Raws::parse(src).unwrap();
+        let mut rng = rand::thread_rng();
+
+        let plain = raws.spawn_table(1, None);
+        let flooded = raws.spawn_table(1, Some(Theme::Flooded));
+
+        assert!((0..20).all(|_| plain.roll(&mut rng).unwrap() == "Rat"));
+        assert!((0..100).any(|_| flooded.roll(&mut rng).unwrap() == "Leech"));
+    }
+
+    #[test]
+    fn parse_rejects_unknown_spawn_table_names() {
+        let src = r#"{
+            "player": { "name": "Player" },
+            "mobs": [{ "name": "Rat" }],
+            "items": [],
+            "npcs": [],
+            "spawn_table": [{ "name": "Bat", "weight": 1, "min_depth": 1, "max_depth": 3 }]
+        }"#;
+
+        assert!(Raws::parse(src).is_err());
+    }
+
+    #[test]
+    fn parse_rejects_duplicate_names() {
+        let src = r#"{
+            "player": { "name": "Player" },
+            "mobs": [{ "name": "Rat" }],
+            "items": [{ "name": "Rat" }],
+            "npcs": []
+        }"#;
+
+        assert!(Raws::parse(src).is_err());
+    }
+}
@@ -0,0 +1,368 @@
+use crate::components as cmp;
+use crate::map::level::Placement;
+use crate::map::pathfinding::DijkstraMap;
+use crate::map::regions::RoomTag;
+use crate::map::theming::Theme;
+use crate::map::{Map, Tile};
+use crate::raws::{RawItem, Raws, Template, TemplateKind};
+use crate::systems::initiative::NORMAL_SPEED;
+use crate::utils::rect::{Rect, SimpleRect};
+use log::warn;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rltk::{Point, RGB};
+use specs::prelude::*;
+use std::collections::HashSet;
+
+/// Most things that can be spawned in a room at depth 1 (deeper rooms get more)
+const MAX_SPAWNS_PER_ROOM: i32 = 4;
+
+/// Template of the keys spawned for locked doors
+const KEY_TEMPLATE: &str = "Key";
+
+/// Monsters don't spawn this close to the player's start (when there's no start room)
+const START_SAFE_RADIUS: f32 = 8.;
+
+/// Parse an html colour from the raws
+fn colour(hex: &str) -> RGB {
+    RGB::from_hex(hex).unwrap_or_else(|_| panic!("Invalid colour in raws: {}", hex))
+}
+
+/// Builds an entity of kind `kind` at `pos`, following `template`
+fn build(world: &mut World, kind: TemplateKind, template: &Template, pos: Point) -> Entity {
+    let mut builder = world
+        .create_entity()
+        .with(cmp::Name::from(template.name.as_str()))
+        .with(cmp::Pos::from(pos));
+
+    // Marker components.
+    builder = match kind {
+        TemplateKind::Player => builder
+            .with(cmp::Player)
+            .with(cmp::HungerClock::new(cmp::HungerState::WellFed)),
+        TemplateKind::Mob => builder.with(cmp::Monster),
+        TemplateKind::Item => builder.with(cmp::Item),
+        TemplateKind::Npc => builder,
+        TemplateKind::Trap => builder.with(cmp::Hidden).with(cmp::EntryTrigger),
+    };
+
+    // Whoever acts on its own needs energy to do so.
+    if let TemplateKind::Player | TemplateKind::Mob = kind {
+        builder = builder.with(cmp::Energy::new(template.speed.unwrap_or(NORMAL_SPEED)));
+    }
+
+    if let Some(renderable) = &template.renderable {
+        builder = builder.with(cmp::Renderable {
+            glyph: rltk::to_cp437(renderable.glyph),
+            fg: colour(&renderable.fg),
+            bg: colour(&renderable.bg),
+        });
+    }
+    if let Some(range) = template.viewshed {
+        builder = builder.with(cmp::Viewshed::new(range));
+    }
+    if let Some(light) = &template.light {
+        builder = builder.with(cmp::LightSource {
+            radius: light.radius,
+            colour: colour(&light.colour),
+        });
+    }
+    if let Some(stats) = &template.stats {
+        builder = builder.with(cmp::CombatStats {
+            max_hp: stats.max_hp,
+            hp: stats.max_hp,
+            defense: stats.defense,
+            power: stats.power,
+        });
+    }
+    if let Some(file) = &template.dialogue {
+        builder = builder.with(cmp::Dialogue { file: file.clone() });
+    }
+
+    // Item behaviour.
+    if let Some(item) = &template.item {
+        if item.consumable {
+            builder = builder.with(cmp::Consumable);
+        }
+        if let Some(range) = item.range {
+            builder = builder.with(cmp::Ranged { range });
+        }
+        if let Some(radius) = item.area_of_effect {
+            builder = builder.with(cmp::AreaOfEffect { radius });
+        }
+        builder = with_effects(builder, item);
+        if item.key {
+            builder = builder.with(cmp::Key);
+        }
+        if let Some(equippable) = &item.equippable {
+            builder = builder.with(cmp::Equippable {
+                slot: equippable.slot,
+            });
+            if let Some(power) = equippable.power_bonus {
+                builder = builder.with(cmp::MeleePowerBonus { power });
+            }
+            if let Some(defense) = equippable.defense_bonus {
+                builder = builder.with(cmp::DefenseBonus { defense });
+            }
+        }
+    }
+
+    // Trap behaviour.
+    if let Some(trap) = &template.trap {
+        if trap.single_use {
+            builder = builder.with(cmp::SingleActivation);
+        }
+        builder = with_effects(builder, &trap.effects);
+    }
+
+    builder.build()
+}
+
+/// Adds the components describing what happens to whoever gets affected by an item or trap
+fn with_effects<'a>(mut builder: EntityBuilder<'a>, effects: &RawItem) -> EntityBuilder<'a> {
+    if let Some(heal_amount) = effects.healing {
+        builder = builder.with(cmp::ProvidesHealing { heal_amount });
+    }
+    if let Some(damage) = effects.damage {
+        builder = builder.with(cmp::InflictsDamage { damage });
+    }
+    if let Some(turns) = effects.confusion {
+        builder = builder.with(cmp::Confusion { turns });
+    }
+    if let Some(turns) = effects.blindness {
+        builder = builder.with(cmp::Blinds { turns });
+    }
+    if let Some(poison) = &effects.poison {
+        builder = builder.with(cmp::InflictsPoison {
+            turns: poison.turns,
+            damage: poison.amount,
+        });
+    }
+    if let Some(regen) = &effects.regeneration {
+        builder = builder.with(cmp::ProvidesRegeneration {
+            turns: regen.turns,
+            amount: regen.amount,
+        });
+    }
+    if effects.food {
+        builder = builder.with(cmp::ProvidesFood);
+    }
+    if effects.teleports {
+        builder = builder.with(cmp::Teleports);
+    }
+
+    builder
+}
+
+/// Spawns the player at `pos`
+pub fn spawn_player(world: &mut World, pos: Point) -> Entity {
+    let template = world.fetch::<Raws>().player().clone();
+
+    build(world, TemplateKind::Player, &template, pos)
+}
+
+/// Spawns entity from template called `name` at `pos` (returns `None` if there's no such
+/// template)
+pub fn spawn_named(world: &mut World, name: &str, pos: Point) -> Option<Entity> {
+    let (kind, template) = {
+        let raws = world.fetch::<Raws>();
+        (raws.kind_of(name)?, raws.get(name)?.clone())
+    };
+
+    Some(build(world, kind, &template, pos))
+}
+
+/// Spawns the entities placed in a hand-made level (skipping unknown templates)
+pub fn spawn_placements(world: &mut World, placements: &[Placement]) {
+    for placement in placements {
+        let pos = Point::new(placement.x, placement.y);
+        if spawn_named(world, &placement.template, pos).is_none() {
+            warn!("Unknown template in level: {}", placement.template);
+        }
+    }
+}
+
+/// Regions to populate (with their theme): the map rooms, or the whole map if the generator
+/// reported none
+fn spawn_regions(map: &Map) -> Vec<(SimpleRect, Option<Theme>)> {
+    if map.rooms.is_empty() {
+        vec![(SimpleRect::from(map), None)]
+    } else {
+        map.rooms
+            .iter()
+            .enumerate()
+            .map(|(id, room)| (SimpleRect::from(room), map.room_themes.get(&id).copied()))
+            .collect()
+    }
+}
+
+/// Pick where the player should start: a random spot in the start room (or in a random room if
+/// none was tagged, or anywhere if there are no rooms)
+pub fn pick_start<R: Rng + ?Sized>(map: &Map, rng: &mut R) -> Point {
+    let start_room = match map.rooms_tagged(RoomTag::Start).first() {
+        Some(&id) => map.rooms.get(id),
+        None => map.rooms.choose(rng),
+    };
+    start_room
+        .and_then(|room| {
+            let room = SimpleRect::from(room);
+            free_tiles(map, &room, &HashSet::new()).choose(rng).copied()
+        })
+        .or_else(|| map.random_passable_tile(rng))
+        .expect("Could not pick player spawn point")
+}
+
+/// Passable tiles of `region` not occupied by anything
+fn free_tiles(map: &Map, region: &SimpleRect, occupied: &HashSet<(i32, i32)>) -> Vec<Point> {
+    let (x, y, w, h) = region.get_corners();
+    (y..y + h)
+        .flat_map(|ty| (x..x + w).map(move |tx| Point::new(tx, ty)))
+        .filter(|p| map.contains_point(p.x, p.y) && map.at(p.x, p.y).is_passable())
+        .filter(|p| !occupied.contains(&(p.x, p.y)))
+        .collect()
+}
+
+/// Passable tiles not occupied by anything that can be walked to from `from` without going
+/// through locked doors
+fn reachable_tiles(map: &Map, from: Point, occupied: &HashSet<(i32, i32)>) -> Vec<Point> {
+    let dijkstra = DijkstraMap::new(map, &HashSet::new(), &[map.xy_idx(from.x, from.y)]);
+    (0..map.tiles.len())
+        .filter(|&idx| dijkstra.get(idx).is_some())
+        .map(|idx| map.idx_xy(idx))
+        .filter(|p| map.at(p.x, p.y).is_passable())
+        .filter(|p| !occupied.contains(&(p.x, p.y)))
+        .collect()
+}
+
+/// Spots for the keys of the locked doors of `map`: in the room containing `start` (or somewhere
+/// reachable from it if it isn't in a room), so that the player can never be locked out
+///
+/// Doors left without a key (because there's no room for it) are unlocked.
+fn place_keys<R: Rng + ?Sized>(
+    map: &mut Map,
+    start: Point,
+    occupied: &HashSet<(i32, i32)>,
+    rng: &mut R,
+) -> Vec<Point> {
+    let start_room = map
+        .rooms
+        .iter()
+        .map(SimpleRect::from)
+        .find(|room| room.contains_point(start.x, start.y));
+    let mut spots = match start_room {
+        Some(room) => free_tiles(map, &room, occupied),
+        None => reachable_tiles(map, start, occupied),
+    };
+    spots.shuffle(rng);
+
+    let locked_doors: Vec<usize> = (0..map.tiles.len())
+        .filter(|&idx| map.tiles[idx] == Tile::LockedDoor)
+        .collect();
+    spots.truncate(locked_doors.len());
+    let keyless = &locked_doors[spots.len()..];
+    if !keyless.is_empty() {
+        warn!("No room for {} keys, unlocking their doors", keyless.len());
+    }
+    for &idx in keyless {
+        let door = map.idx_xy(idx);
+        *map.at_mut(door.x, door.y) = Tile::ClosedDoor;
+    }
+
+    spots
+}
+
+/// Fills `map` with stuff rolled from the spawn table of `depth`, room by room (themed rooms
+/// also roll the entries of their theme).
+///
+/// Things only spawn on free passable tiles, and no monster (or trap) is spawned in the room
+/// containing `start` (or near it, if the map has no rooms). Keys for the locked doors are left
+/// near `start` (see `place_keys`).
+pub fn populate<R: Rng + ?Sized>(
+    world: &mut World,
+    map: &mut Map,
+    depth: i32,
+    start: Point,
+    rng: &mut R,
+) {
+    // Don't spawn on top of what's already there.
+    let mut occupied: HashSet<(i32, i32)> = world
+        .read_storage::<cmp::Pos>()
+        .join()
+        .map(|pos| (pos.x, pos.y))
+        .collect();
+
+    // Keys first, so that there's room for them.
+    for pos in place_keys(map, start, &occupied, rng) {
+        spawn_named(world, KEY_TEMPLATE, pos);
+        occupied.insert((pos.x, pos.y));
+    }
+
+    for (region, theme) in spawn_regions(map) {
+        let table = world.fetch::<Raws>().spawn_table(depth, theme);
+        if table.is_empty() {
+            continue;
+        }
+        let is_start_region = !map.rooms.is_empty() && region.contains_point(start.x, start.y);
+        let spawn_count = rng.gen_range(0, MAX_SPAWNS_PER_ROOM + depth);
+
+        for _ in 0..spawn_count {
+            let name = table.roll(rng).expect("Empty spawn table").clone();
+
+            // Keep monsters and traps away from the player's start.
+            let is_hostile = matches!(
+                world.fetch::<Raws>().kind_of(&name),
+                Some(TemplateKind::Mob) | Some(TemplateKind::Trap)
+            );
+            let mut candidates = free_tiles(map, &region, &occupied);
+            if is_hostile {
+                if is_start_region {
+                    continue;
+                }
+                candidates.retain(|&p| {
+                    rltk::DistanceAlg::Pythagoras.distance2d(start, p) > START_SAFE_RADIUS
+                });
+            }
+
+            let pos = match candidates.choose(rng) {
+                Some(&pos) => pos,
+                // Room is full.
+                None => break,
+            };
+            spawn_named(world, &name, pos);
+            occupied.insert((pos.x, pos.y));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Room;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn doors_without_a_key_are_unlocked() {
+        // Tiny start room, leading to a corridor with two locked doors.
+        let mut map = Map::all(8, 3, Tile::Wall);
+        map.add_room(Room {
+            x: 1,
+            y: 1,
+            width: 1,
+            height: 1,
+        });
+        for x in 2..7 {
+            *map.at_mut(x, 1) = Tile::Floor;
+        }
+        *map.at_mut(3, 1) = Tile::LockedDoor;
+        *map.at_mut(5, 1) = Tile::LockedDoor;
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let keys = place_keys(&mut map, Point::new(1, 1), &HashSet::new(), &mut rng);
+
+        assert_eq!(keys, vec![Point::new(1, 1)]);
+        let locked = map.tiles.iter().filter(|&&t| t == Tile::LockedDoor).count();
+        assert_eq!(locked, 1);
+        assert_eq!(map.at(5, 1), Tile::ClosedDoor);
+    }
+}
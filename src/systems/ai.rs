@@ -1,5 +1,142 @@
+use crate::components as cmp;
+use crate::map::pathfinding::Pathfinder;
+use crate::map::{Map, Tile};
+use crate::systems::initiative::{ATTACK_COST, MOVE_COST, WAIT_COST};
+use crate::utils::dir::Dir;
+use rand::Rng;
+use rltk::Point;
 use specs::prelude::*;
+use std::collections::HashSet;
 
+/// Monsters run away when their hit points drop to this fraction of the maximum (or below)
+const FLEE_HP_FRACTION: i32 = 4;
+
+/// Makes monsters whose turn it is act: they attack the player when adjacent and chase
+/// him/her when in sight (or run away when badly hurt), opening doors on the way.
+///
+/// Every non-player entity whose turn it is gets its turn ended (and energy spent).
 pub struct AI;
 
+impl<'a> System<'a> for AI {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        WriteExpect<'a, Map>,
+        WriteExpect<'a, Pathfinder>,
+        WriteStorage<'a, cmp::Energy>,
+        WriteStorage<'a, cmp::MyTurn>,
+        WriteStorage<'a, cmp::Pos>,
+        WriteStorage<'a, cmp::WantsToMelee>,
+        WriteStorage<'a, cmp::EntityMoved>,
+        ReadStorage<'a, cmp::Viewshed>,
+        ReadStorage<'a, cmp::Monster>,
+        ReadStorage<'a, cmp::Player>,
+        ReadStorage<'a, cmp::Confused>,
+        ReadStorage<'a, cmp::CombatStats>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut map,
+            mut pathfinder,
+            mut energies,
+            mut turns,
+            mut positions,
+            mut wants_melee,
+            mut moved,
+            viewsheds,
+            monsters,
+            players,
+            confused,
+            stats,
+        ): Self::SystemData,
+    ) {
+        let (player, player_pos) = match (&entities, &positions, &players).join().next() {
+            Some((player, pos, _)) => (player, Point::new(pos.x, pos.y)),
+            None => return,
+        };
+
+        // Fighters block each other.
+        let mut occupied: HashSet<(i32, i32)> = (&positions, &stats)
+            .join()
+            .map(|(pos, _)| (pos.x, pos.y))
+            .collect();
+
+        let actors: Vec<Entity> = (&entities, &turns, !&players)
+            .join()
+            .map(|(e, _, _)| e)
+            .collect();
+        for actor in actors {
+            let here = positions.get(actor).map(|pos| Point::new(pos.x, pos.y));
+            let cost = match (monsters.contains(actor), here, viewsheds.get(actor)) {
+                (true, Some(here), Some(viewshed)) => {
+                    let adjacent = map.is_adjacent(here, player_pos);
+                    let sees_player = viewshed.visible_tiles.contains(&player_pos);
+                    let is_confused = confused.contains(actor);
+                    let fleeing = sees_player
+                        && !is_confused
+                        && stats
+                            .get(actor)
+                            .map_or(false, |s| s.hp <= s.max_hp / FLEE_HP_FRACTION);
+
+                    if adjacent && !is_confused && !fleeing {
+                        wants_melee
+                            .insert(actor, cmp::WantsToMelee { target: player })
+                            .expect("Unable to insert want to melee");
+                        ATTACK_COST
+                    } else if sees_player || is_confused {
+                        // Chase the player, run away or stumble around if confused.
+                        let dest = if is_confused {
+                            let (dx, dy) = rand::thread_rng().gen::<Dir>().delta();
+                            Some((here.x + dx, here.y + dy))
+                        } else if fleeing {
+                            pathfinder
+                                .step_away(&map, &[player_pos], here)
+                                .map(|p| (p.x, p.y))
+                        } else {
+                            pathfinder
+                                .step_towards(&map, &[player_pos], here)
+                                .map(|p| (p.x, p.y))
+                        };
+
+                        let dest = dest.filter(|&(x, y)| {
+                            Dir::from_delta((x - here.x, y - here.y))
+                                .and_then(|dir| map.step_cost(here, dir))
+                                .is_some()
+                                && !occupied.contains(&(x, y))
+                        });
+                        if let Some(dest) = dest.filter(|&(x, y)| map.at(x, y) == Tile::ClosedDoor)
+                        {
+                            // Doors in the way get opened first.
+                            *map.at_mut(dest.0, dest.1) = Tile::OpenDoor;
+                            MOVE_COST
+                        } else if let Some(dest) = dest {
+                            occupied.remove(&(here.x, here.y));
+                            occupied.insert(dest);
+                            let pos = positions.get_mut(actor).expect("Monster lost its position");
+                            pos.x = dest.0;
+                            pos.y = dest.1;
+                            moved
+                                .insert(actor, cmp::EntityMoved)
+                                .expect("Unable to insert entity moved");
+                            MOVE_COST
+                        } else {
+                            WAIT_COST
+                        }
+                    } else {
+                        WAIT_COST
+                    }
+                }
+                // Not a monster (or a monster that can't do anything): just wait.
+                _ => WAIT_COST,
+            };
+
+            if let Some(energy) = energies.get_mut(actor) {
+                energy.energy -= cost;
+            }
+            turns.remove(actor);
+        }
+    }
 }
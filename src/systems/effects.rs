@@ -0,0 +1,151 @@
+use crate::components as cmp;
+use crate::effects::{Effect, EffectQueue};
+use crate::gamelog::GameLog;
+use crate::map::Map;
+use specs::prelude::*;
+use std::collections::HashSet;
+
+/// Applies all queued effects to their targets.
+pub struct ApplyEffects;
+
+impl<'a> System<'a> for ApplyEffects {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Map>,
+        WriteExpect<'a, EffectQueue>,
+        WriteExpect<'a, GameLog>,
+        WriteStorage<'a, cmp::CombatStats>,
+        WriteStorage<'a, cmp::Confused>,
+        WriteStorage<'a, cmp::Blind>,
+        WriteStorage<'a, cmp::Poisoned>,
+        WriteStorage<'a, cmp::Regenerating>,
+        WriteStorage<'a, cmp::HungerClock>,
+        WriteStorage<'a, cmp::Pos>,
+        ReadStorage<'a, cmp::Name>,
+        ReadStorage<'a, cmp::Invulnerable>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            map,
+            mut queue,
+            mut log,
+            mut stats,
+            mut confused,
+            mut blind,
+            mut poisoned,
+            mut regenerating,
+            mut hunger,
+            mut positions,
+            names,
+            invulnerable,
+        ): Self::SystemData,
+    ) {
+        for event in queue.drain() {
+            let target = event.target;
+
+            // Target might have died because of a previous effect.
+            if !entities.is_alive(target) {
+                continue;
+            }
+            let name = names.get(target).map_or("Something", |n| n.name.as_str());
+
+            match event.effect {
+                Effect::Heal(amount) => {
+                    if let Some(stats) = stats.get_mut(target) {
+                        stats.hp = std::cmp::min(stats.max_hp, stats.hp + amount);
+                        log.log(format!("{} heals {} hp.", name, amount));
+                    }
+                }
+                Effect::Damage(_) if invulnerable.contains(target) => {
+                    log.log(format!("{} is unharmed.", name));
+                }
+                Effect::Damage(amount) => {
+                    if let Some(stats) = stats.get_mut(target) {
+                        stats.hp -= amount;
+                        log.log(format!("{} takes {} damage.", name, amount));
+                    }
+                }
+                // Only fighters are affected by status effects.
+                Effect::Confuse(_)
+                | Effect::Blind(_)
+                | Effect::Poison { .. }
+                | Effect::Regenerate { .. }
+                    if !stats.contains(target) => {}
+                Effect::Confuse(turns) => {
+                    confused
+                        .insert(target, cmp::Confused { turns })
+                        .expect("Unable to confuse target");
+                    log.log(format!("{} is confused.", name));
+                }
+                Effect::Blind(turns) => {
+                    blind
+                        .insert(target, cmp::Blind { turns })
+                        .expect("Unable to blind target");
+                    log.log(format!("{} is blinded.", name));
+                }
+                Effect::Poison { turns, damage } => {
+                    poisoned
+                        .insert(target, cmp::Poisoned { turns, damage })
+                        .expect("Unable to poison target");
+                    log.log(format!("{} is poisoned.", name));
+                }
+                Effect::Regenerate { turns, amount } => {
+                    regenerating
+                        .insert(target, cmp::Regenerating { turns, amount })
+                        .expect("Unable to make target regenerate");
+                    log.log(format!("{} starts regenerating.", name));
+                }
+                Effect::Feed => {
+                    if let Some(clock) = hunger.get_mut(target) {
+                        *clock = cmp::HungerClock::new(cmp::HungerState::WellFed);
+                        log.log(format!("{} eats.", name));
+                        log.log(clock.state.message());
+                    }
+                }
+                Effect::Teleport => {
+                    // Don't land on someone else.
+                    let blocked: HashSet<usize> = (&positions, &stats)
+                        .join()
+                        .map(|(pos, _)| map.xy_idx(pos.x, pos.y))
+                        .collect();
+                    let destination = map.random_free_tile(&blocked, &mut rand::thread_rng());
+                    if let (Some(pos), Some(destination)) = (positions.get_mut(target), destination)
+                    {
+                        *pos = cmp::Pos::from(destination);
+                        log.log(format!("{} vanishes!", name));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Removes entities that ran out of hit points (except the player, who is handled by the game
+/// state).
+pub struct Reaper;
+
+impl<'a> System<'a> for Reaper {
+    type SystemData = (
+        Entities<'a>,
+        WriteExpect<'a, GameLog>,
+        ReadStorage<'a, cmp::CombatStats>,
+        ReadStorage<'a, cmp::Name>,
+        ReadStorage<'a, cmp::Player>,
+    );
+
+    fn run(&mut self, (entities, mut log, stats, names, players): Self::SystemData) {
+        for (entity, stats, _) in (&entities, &stats, !&players).join() {
+            if stats.hp <= 0 {
+                let name = names.get(entity).map_or("Something", |n| n.name.as_str());
+                log.log(format!("{} dies.", name));
+                entities
+                    .delete(entity)
+                    .expect("Unable to delete dead entity");
+            }
+        }
+    }
+}
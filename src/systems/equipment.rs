@@ -0,0 +1,112 @@
+use crate::components as cmp;
+use crate::gamelog::GameLog;
+use crate::systems::inventory::name_of;
+use specs::prelude::*;
+
+/// Moves items that entities want to equip from their backpacks to their equipment slots
+/// (putting whatever was in the slot back in the backpack).
+pub struct ItemEquip;
+
+impl<'a> System<'a> for ItemEquip {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        WriteExpect<'a, GameLog>,
+        WriteStorage<'a, cmp::WantsToEquip>,
+        WriteStorage<'a, cmp::Equipped>,
+        WriteStorage<'a, cmp::InBackpack>,
+        ReadStorage<'a, cmp::Equippable>,
+        ReadStorage<'a, cmp::Name>,
+        ReadStorage<'a, cmp::Player>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut log,
+            mut wants_equip,
+            mut equipped,
+            mut backpacks,
+            equippables,
+            names,
+            players,
+        ): Self::SystemData,
+    ) {
+        for (owner, equip) in (&entities, &wants_equip).join() {
+            let item = equip.item;
+
+            // Only equip equippable stuff from one's own backpack.
+            let slot = match (equippables.get(item), backpacks.get(item)) {
+                (Some(equippable), Some(backpack)) if backpack.owner == owner => equippable.slot,
+                _ => continue,
+            };
+
+            // Free the slot.
+            let old_items: Vec<Entity> = (&entities, &equipped)
+                .join()
+                .filter(|(_, eq)| eq.owner == owner && eq.slot == slot)
+                .map(|(old_item, _)| old_item)
+                .collect();
+            for old_item in old_items {
+                equipped.remove(old_item);
+                backpacks
+                    .insert(old_item, cmp::InBackpack { owner })
+                    .expect("Unable to put unequipped item in backpack");
+                if players.contains(owner) {
+                    log.log(format!("You unequip the {}.", name_of(&names, old_item)));
+                }
+            }
+
+            // Wear the new item.
+            backpacks.remove(item);
+            equipped
+                .insert(item, cmp::Equipped { owner, slot })
+                .expect("Unable to equip item");
+            if players.contains(owner) {
+                log.log(format!("You equip the {}.", name_of(&names, item)));
+            }
+        }
+
+        wants_equip.clear();
+    }
+}
+
+/// Moves equipped items that entities want to take off back to their backpacks.
+pub struct ItemUnequip;
+
+impl<'a> System<'a> for ItemUnequip {
+    type SystemData = (
+        Entities<'a>,
+        WriteExpect<'a, GameLog>,
+        WriteStorage<'a, cmp::WantsToUnequip>,
+        WriteStorage<'a, cmp::Equipped>,
+        WriteStorage<'a, cmp::InBackpack>,
+        ReadStorage<'a, cmp::Name>,
+        ReadStorage<'a, cmp::Player>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut log, mut wants_unequip, mut equipped, mut backpacks, names, players): Self::SystemData,
+    ) {
+        for (owner, unequip) in (&entities, &wants_unequip).join() {
+            let item = unequip.item;
+
+            match equipped.get(item) {
+                Some(eq) if eq.owner == owner => {}
+                _ => continue,
+            }
+
+            equipped.remove(item);
+            backpacks
+                .insert(item, cmp::InBackpack { owner })
+                .expect("Unable to put unequipped item in backpack");
+            if players.contains(owner) {
+                log.log(format!("You unequip the {}.", name_of(&names, item)));
+            }
+        }
+
+        wants_unequip.clear();
+    }
+}
@@ -0,0 +1,169 @@
+use crate::components as cmp;
+use specs::prelude::*;
+
+/// Energy gained per clock tick by entities of normal speed
+pub const NORMAL_SPEED: i32 = 10;
+/// Energy needed to act
+pub const ACTION_THRESHOLD: i32 = 100;
+/// Clock ticks in a game turn (i.e. the time needed by a normal entity to do a normal action)
+pub const TICKS_PER_TURN: u64 = (ACTION_THRESHOLD / NORMAL_SPEED) as u64;
+
+/// Energy spent by the various actions
+pub const MOVE_COST: i32 = 100;
+pub const ATTACK_COST: i32 = 100;
+pub const WAIT_COST: i32 = 100;
+pub const PICKUP_COST: i32 = 50;
+pub const DROP_COST: i32 = 50;
+pub const USE_ITEM_COST: i32 = 100;
+pub const EQUIP_COST: i32 = 150;
+
+/// Most times the world is allowed to act in a single frame
+pub const MAX_WORLD_STEPS: usize = 1000;
+
+/// Keeps track of game time
+#[derive(Default)]
+pub struct Clock {
+    /// Clock ticks since the start of the game
+    pub ticks: u64,
+    /// Game turns that passed and that still need to be processed (see `TICKS_PER_TURN`)
+    pub pending_turns: u32,
+}
+
+/// Decides who acts next: advances the clock, giving energy to everyone, until someone has
+/// enough energy to act (and gives them `MyTurn`).
+///
+/// Does nothing if someone is still in the middle of their turn.
+pub struct Scheduler;
+
+impl<'a> System<'a> for Scheduler {
+    type SystemData = (
+        Entities<'a>,
+        WriteExpect<'a, Clock>,
+        WriteStorage<'a, cmp::Energy>,
+        WriteStorage<'a, cmp::MyTurn>,
+    );
+
+    fn run(&mut self, (entities, mut clock, mut energies, mut turns): Self::SystemData) {
+        if turns.join().next().is_some() {
+            return;
+        }
+
+        // Nobody would ever act...
+        if !energies.join().any(|e| e.speed > 0) {
+            return;
+        }
+
+        loop {
+            clock.ticks += 1;
+            if clock.ticks % TICKS_PER_TURN == 0 {
+                clock.pending_turns += 1;
+            }
+
+            let mut someone_ready = false;
+            for (entity, energy) in (&entities, &mut energies).join() {
+                energy.energy += energy.speed;
+                if energy.energy >= ACTION_THRESHOLD {
+                    turns
+                        .insert(entity, cmp::MyTurn)
+                        .expect("Unable to give turn");
+                    someone_ready = true;
+                }
+            }
+
+            if someone_ready {
+                break;
+            }
+        }
+    }
+}
+
+/// Runs `step` until it says it's done, at most `MAX_WORLD_STEPS` times (so that a broken schedule
+/// can't freeze the game). Returns whether `step` got done.
+pub fn run_bounded(mut step: impl FnMut() -> bool) -> bool {
+    (0..MAX_WORLD_STEPS).any(|_| step())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// World with a clock and an actor for each of `speeds`
+    fn world_with(speeds: &[i32]) -> (World, Vec<Entity>) {
+        let mut world = World::new();
+        world.register::<cmp::Energy>();
+        world.register::<cmp::MyTurn>();
+        world.insert(Clock::default());
+        let actors = speeds
+            .iter()
+            .map(|&speed| world.create_entity().with(cmp::Energy::new(speed)).build())
+            .collect();
+        (world, actors)
+    }
+
+    /// Runs the scheduler once, then ends the turn of everyone who got one (spending `cost`).
+    /// Returns who acted.
+    fn act(world: &World, cost: i32) -> Vec<Entity> {
+        Scheduler.run_now(world);
+        let entities = world.entities();
+        let mut energies = world.write_storage::<cmp::Energy>();
+        let mut turns = world.write_storage::<cmp::MyTurn>();
+        let acted: Vec<Entity> = (&entities, &turns).join().map(|(e, _)| e).collect();
+        for &actor in &acted {
+            energies.get_mut(actor).unwrap().energy -= cost;
+            turns.remove(actor);
+        }
+        acted
+    }
+
+    #[test]
+    fn faster_actors_act_more_often() {
+        let (world, actors) = world_with(&[NORMAL_SPEED, 15]);
+        let mut counts = [0, 0];
+        while world.fetch::<Clock>().ticks < 60 * TICKS_PER_TURN {
+            for actor in act(&world, MOVE_COST) {
+                counts[actors.iter().position(|&a| a == actor).unwrap()] += 1;
+            }
+        }
+
+        assert_eq!(counts, [60, 90]);
+        assert_eq!(world.fetch::<Clock>().pending_turns, 60);
+    }
+
+    #[test]
+    fn actors_ready_together_act_on_the_same_tick() {
+        let (world, actors) = world_with(&[NORMAL_SPEED, NORMAL_SPEED]);
+        Scheduler.run_now(&world);
+
+        let ticks = world.fetch::<Clock>().ticks;
+        assert_eq!(ticks, TICKS_PER_TURN);
+        assert!(actors
+            .iter()
+            .all(|&a| world.read_storage::<cmp::MyTurn>().contains(a)));
+
+        // Time stands still until everybody is done.
+        world.write_storage::<cmp::MyTurn>().remove(actors[0]);
+        Scheduler.run_now(&world);
+        assert_eq!(world.fetch::<Clock>().ticks, ticks);
+        assert!(!world.read_storage::<cmp::MyTurn>().contains(actors[0]));
+    }
+
+    #[test]
+    fn world_steps_are_bounded() {
+        // The first actor (i.e. the player) can never act.
+        let (world, actors) = world_with(&[0, NORMAL_SPEED]);
+        let mut steps = 0;
+
+        let player_acted = run_bounded(|| {
+            steps += 1;
+            act(&world, WAIT_COST).contains(&actors[0])
+        });
+
+        assert!(!player_acted);
+        assert_eq!(steps, MAX_WORLD_STEPS);
+        assert_eq!(
+            world.fetch::<Clock>().ticks,
+            MAX_WORLD_STEPS as u64 * TICKS_PER_TURN
+        );
+        assert!(run_bounded(|| true));
+    }
+}
@@ -0,0 +1,90 @@
+use crate::components as cmp;
+use crate::gamelog::GameLog;
+use specs::prelude::*;
+
+/// Moves items that entities want to pick up from the map to their backpacks.
+pub struct ItemCollection;
+
+impl<'a> System<'a> for ItemCollection {
+    type SystemData = (
+        Entities<'a>,
+        WriteExpect<'a, GameLog>,
+        WriteStorage<'a, cmp::WantsToPickup>,
+        WriteStorage<'a, cmp::Pos>,
+        WriteStorage<'a, cmp::InBackpack>,
+        ReadStorage<'a, cmp::Name>,
+        ReadStorage<'a, cmp::Player>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut log, mut wants_pickup, mut positions, mut backpacks, names, players): Self::SystemData,
+    ) {
+        for (collector, pickup) in (&entities, &wants_pickup).join() {
+            // The item might have been taken by someone else in the meantime.
+            if positions.remove(pickup.item).is_none() {
+                continue;
+            }
+            backpacks
+                .insert(pickup.item, cmp::InBackpack { owner: collector })
+                .expect("Unable to put item in backpack");
+
+            if players.contains(collector) {
+                log.log(format!("You pick up the {}.", name_of(&names, pickup.item)));
+            }
+        }
+
+        wants_pickup.clear();
+    }
+}
+
+/// Moves items that entities want to drop from their backpacks to the map (under the dropper).
+pub struct ItemDrop;
+
+impl<'a> System<'a> for ItemDrop {
+    type SystemData = (
+        Entities<'a>,
+        WriteExpect<'a, GameLog>,
+        WriteStorage<'a, cmp::WantsToDrop>,
+        WriteStorage<'a, cmp::Pos>,
+        WriteStorage<'a, cmp::InBackpack>,
+        ReadStorage<'a, cmp::Name>,
+        ReadStorage<'a, cmp::Player>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut log, mut wants_drop, mut positions, mut backpacks, names, players): Self::SystemData,
+    ) {
+        for (dropper, drop) in (&entities, &wants_drop).join() {
+            // Only drop what is actually owned.
+            match backpacks.get(drop.item) {
+                Some(backpack) if backpack.owner == dropper => {}
+                _ => continue,
+            }
+
+            let drop_pos = match positions.get(dropper) {
+                Some(pos) => cmp::Pos { x: pos.x, y: pos.y },
+                None => continue,
+            };
+            positions
+                .insert(drop.item, drop_pos)
+                .expect("Unable to drop item on map");
+            backpacks.remove(drop.item);
+
+            if players.contains(dropper) {
+                log.log(format!("You drop the {}.", name_of(&names, drop.item)));
+            }
+        }
+
+        wants_drop.clear();
+    }
+}
+
+/// Name of entity (or a placeholder if it has none)
+pub fn name_of(names: &ReadStorage<cmp::Name>, entity: Entity) -> String {
+    names
+        .get(entity)
+        .map(|n| n.name.clone())
+        .unwrap_or_else(|| "thing".to_string())
+}
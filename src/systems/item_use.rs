@@ -0,0 +1,101 @@
+use crate::components as cmp;
+use crate::effects::{self, EffectQueue, EffectSources};
+use crate::gamelog::GameLog;
+use crate::map::Map;
+use crate::utils::rect::Rect;
+use rltk::Point;
+use specs::prelude::*;
+
+/// Turns the items entities want to use into effects on their targets.
+pub struct ItemUse;
+
+impl<'a> System<'a> for ItemUse {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Map>,
+        WriteExpect<'a, EffectQueue>,
+        WriteExpect<'a, GameLog>,
+        WriteStorage<'a, cmp::WantsToUseItem>,
+        ReadStorage<'a, cmp::Pos>,
+        ReadStorage<'a, cmp::Name>,
+        ReadStorage<'a, cmp::Player>,
+        ReadStorage<'a, cmp::CombatStats>,
+        ReadStorage<'a, cmp::Hidden>,
+        ReadStorage<'a, cmp::InBackpack>,
+        ReadStorage<'a, cmp::Consumable>,
+        ReadStorage<'a, cmp::AreaOfEffect>,
+        EffectSources<'a>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            map,
+            mut queue,
+            mut log,
+            mut wants_use,
+            positions,
+            names,
+            players,
+            stats,
+            hidden,
+            backpacks,
+            consumables,
+            aoes,
+            sources,
+        ): Self::SystemData,
+    ) {
+        for (user, use_item) in (&entities, &wants_use).join() {
+            let item = use_item.item;
+
+            // Only items from the user's own backpack can be used.
+            if backpacks.get(item).map_or(true, |b| b.owner != user) {
+                continue;
+            }
+
+            // Find out who is affected: the user itself, or whoever stands on the targeted tiles
+            // (only fighters: items and unnoticed traps are left alone).
+            let targets: Vec<Entity> = match use_item.target {
+                None => vec![user],
+                Some(target) => {
+                    let tiles = match aoes.get(item) {
+                        None => vec![target],
+                        Some(aoe) => {
+                            let map = &*map;
+                            let mut tiles = rltk::field_of_view(target, aoe.radius, map);
+                            tiles.retain(|p| map.contains_point(p.x, p.y));
+                            tiles
+                        }
+                    };
+
+                    (&entities, &positions, &stats, !&hidden)
+                        .join()
+                        .filter(|(_, pos, _, _)| tiles.contains(&Point::new(pos.x, pos.y)))
+                        .map(|(target, _, _, _)| target)
+                        .collect()
+                }
+            };
+
+            let effects = effects::effects_of(&sources, item);
+
+            if players.contains(user) {
+                let name = names.get(item).map_or("thing", |n| n.name.as_str());
+                log.log(format!("You use the {}.", name));
+            }
+
+            for &target in &targets {
+                for &effect in &effects {
+                    queue.push(target, effect);
+                }
+            }
+
+            if consumables.contains(item) {
+                entities.delete(item).expect("Unable to consume item");
+            }
+        }
+
+        wants_use.clear();
+    }
+}
@@ -0,0 +1,47 @@
+use crate::components as cmp;
+use crate::map::Map;
+use crate::utils::rect::Rect;
+use rltk::{Point, RGB};
+use specs::prelude::*;
+
+/// Recomputes the light cast on the map by light sources.
+pub struct Lighting;
+
+impl<'a> System<'a> for Lighting {
+    type SystemData = (
+        WriteExpect<'a, Map>,
+        ReadStorage<'a, cmp::LightSource>,
+        ReadStorage<'a, cmp::Pos>,
+    );
+
+    fn run(&mut self, (mut map, lights, positions): Self::SystemData) {
+        let map = &mut *map;
+        map.light
+            .iter_mut()
+            .for_each(|l| *l = RGB::named(rltk::BLACK));
+
+        for (light, pos) in (&lights, &positions).join() {
+            let origin = Point::new(pos.x, pos.y);
+            let lit_tiles = rltk::field_of_view(origin, light.radius, &*map);
+            for tile in lit_tiles {
+                if !map.contains_point(tile.x, tile.y) {
+                    continue;
+                }
+
+                // Light fades linearly with distance and adds up with other lights.
+                let distance = rltk::DistanceAlg::Pythagoras.distance2d(origin, tile);
+                let intensity = 1. - distance / (light.radius + 1) as f32;
+                if intensity <= 0. {
+                    continue;
+                }
+                let idx = map.xy_idx(tile.x, tile.y);
+                let current = map.light[idx];
+                map.light[idx] = RGB::from_f32(
+                    f32::min(1., current.r + light.colour.r * intensity),
+                    f32::min(1., current.g + light.colour.g * intensity),
+                    f32::min(1., current.b + light.colour.b * intensity),
+                );
+            }
+        }
+    }
+}
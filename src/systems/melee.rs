@@ -0,0 +1,75 @@
+use crate::components as cmp;
+use crate::effects::{Effect, EffectQueue};
+use crate::gamelog::GameLog;
+use specs::prelude::*;
+
+/// Resolves melee attacks, turning them into damage effects.
+///
+/// Power and defense include the bonuses of everything the fighters have equipped.
+pub struct MeleeCombat;
+
+impl<'a> System<'a> for MeleeCombat {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        WriteExpect<'a, GameLog>,
+        WriteExpect<'a, EffectQueue>,
+        WriteStorage<'a, cmp::WantsToMelee>,
+        ReadStorage<'a, cmp::CombatStats>,
+        ReadStorage<'a, cmp::Name>,
+        ReadStorage<'a, cmp::Equipped>,
+        ReadStorage<'a, cmp::MeleePowerBonus>,
+        ReadStorage<'a, cmp::DefenseBonus>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut log,
+            mut queue,
+            mut wants_melee,
+            stats,
+            names,
+            equipped,
+            power_bonuses,
+            defense_bonuses,
+        ): Self::SystemData,
+    ) {
+        for (attacker, melee) in (&entities, &wants_melee).join() {
+            let target = melee.target;
+            let (attacker_stats, target_stats) = match (stats.get(attacker), stats.get(target)) {
+                (Some(a), Some(t)) if a.hp > 0 && t.hp > 0 => (a, t),
+                _ => continue,
+            };
+
+            // Sum the bonuses of whatever is equipped by `owner`.
+            let equipment_bonus = |owner: Entity, bonus: &dyn Fn(Entity) -> Option<i32>| -> i32 {
+                (&entities, &equipped)
+                    .join()
+                    .filter(|(_, eq)| eq.owner == owner)
+                    .filter_map(|(item, _)| bonus(item))
+                    .sum()
+            };
+            let power = attacker_stats.power
+                + equipment_bonus(attacker, &|item| power_bonuses.get(item).map(|b| b.power));
+            let defense = target_stats.defense
+                + equipment_bonus(target, &|item| defense_bonuses.get(item).map(|b| b.defense));
+
+            let attacker_name = names.get(attacker).map_or("Something", |n| n.name.as_str());
+            let target_name = names.get(target).map_or("something", |n| n.name.as_str());
+            let damage = std::cmp::max(0, power - defense);
+            if damage == 0 {
+                log.log(format!(
+                    "{} is unable to hurt {}.",
+                    attacker_name, target_name
+                ));
+            } else {
+                log.log(format!("{} hits {}.", attacker_name, target_name));
+                queue.push(target, Effect::Damage(damage));
+            }
+        }
+
+        wants_melee.clear();
+    }
+}
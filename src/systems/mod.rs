@@ -1,4 +1,25 @@
 pub mod visibility;
+pub mod lighting;
+pub mod pathing;
 pub mod ai;
+pub mod initiative;
+pub mod inventory;
+pub mod equipment;
+pub mod item_use;
+pub mod melee;
+pub mod effects;
+pub mod status;
+pub mod traps;
 
 pub use visibility::Visibility;
+pub use lighting::Lighting;
+pub use pathing::PathIndexing;
+pub use ai::AI;
+pub use initiative::Scheduler;
+pub use inventory::{ItemCollection, ItemDrop};
+pub use equipment::{ItemEquip, ItemUnequip};
+pub use item_use::ItemUse;
+pub use melee::MeleeCombat;
+pub use effects::{ApplyEffects, Reaper};
+pub use status::{StatusEffects, TickStatus};
+pub use traps::{Perception, TrapTrigger};
@@ -0,0 +1,26 @@
+use crate::components as cmp;
+use crate::map::pathfinding::Pathfinder;
+use crate::map::Map;
+use specs::prelude::*;
+use std::collections::HashSet;
+
+/// Keeps the pathfinder in sync with the map and with the tiles occupied by fighters.
+pub struct PathIndexing;
+
+impl<'a> System<'a> for PathIndexing {
+    type SystemData = (
+        ReadExpect<'a, Map>,
+        WriteExpect<'a, Pathfinder>,
+        ReadStorage<'a, cmp::Pos>,
+        ReadStorage<'a, cmp::CombatStats>,
+    );
+
+    fn run(&mut self, (map, mut pathfinder, positions, stats): Self::SystemData) {
+        let blocked: HashSet<usize> = (&positions, &stats)
+            .join()
+            .map(|(pos, _)| map.xy_idx(pos.x, pos.y))
+            .collect();
+
+        pathfinder.update(&map, blocked);
+    }
+}
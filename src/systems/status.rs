@@ -0,0 +1,78 @@
+use crate::components::{self as cmp, HungerState, TimedStatus};
+use crate::effects::{Effect, EffectQueue};
+use crate::gamelog::GameLog;
+use specs::prelude::*;
+use std::marker::PhantomData;
+
+/// Damage taken each turn while starving
+const STARVATION_DAMAGE: i32 = 1;
+
+/// Applies the per-turn effects of statuses (poison, regeneration, starvation).
+///
+/// Meant to be run once per game turn.
+pub struct StatusEffects;
+
+impl<'a> System<'a> for StatusEffects {
+    type SystemData = (
+        Entities<'a>,
+        WriteExpect<'a, EffectQueue>,
+        ReadStorage<'a, cmp::Poisoned>,
+        ReadStorage<'a, cmp::Regenerating>,
+        ReadStorage<'a, cmp::HungerClock>,
+    );
+
+    fn run(&mut self, (entities, mut queue, poisoned, regenerating, hunger): Self::SystemData) {
+        for (entity, poison) in (&entities, &poisoned).join() {
+            queue.push(entity, Effect::Damage(poison.damage));
+        }
+        for (entity, regen) in (&entities, &regenerating).join() {
+            queue.push(entity, Effect::Heal(regen.amount));
+        }
+        for (entity, clock) in (&entities, &hunger).join() {
+            if clock.state == HungerState::Starving {
+                queue.push(entity, Effect::Damage(STARVATION_DAMAGE));
+            }
+        }
+    }
+}
+
+/// Counts down the turns left to every status of type `S`, removing the ones that run out.
+///
+/// Meant to be run once per game turn.
+pub struct TickStatus<S>(PhantomData<S>);
+
+impl<S> Default for TickStatus<S> {
+    fn default() -> Self {
+        TickStatus(PhantomData)
+    }
+}
+
+impl<'a, S: TimedStatus> System<'a> for TickStatus<S> {
+    type SystemData = (
+        Entities<'a>,
+        WriteExpect<'a, GameLog>,
+        WriteStorage<'a, S>,
+        ReadStorage<'a, cmp::Player>,
+    );
+
+    fn run(&mut self, (entities, mut log, mut statuses, players): Self::SystemData) {
+        let mut expired = vec![];
+        for (entity, status) in (&entities, &mut statuses).join() {
+            *status.turns_mut() -= 1;
+            if *status.turns_mut() > 0 {
+                continue;
+            }
+
+            if !status.on_expire() {
+                expired.push(entity);
+            }
+            if players.contains(entity) {
+                log.log(status.expire_message());
+            }
+        }
+
+        for entity in expired {
+            statuses.remove(entity);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use crate::components as cmp;
+use crate::effects::{self, EffectQueue, EffectSources};
+use crate::gamelog::GameLog;
+use crate::systems::inventory::name_of;
+use rand::Rng;
+use rltk::Point;
+use specs::prelude::*;
+
+/// Chance (in percent) for the player to spot each hidden thing in view, every turn
+const SPOT_CHANCE: i32 = 15;
+
+/// Fires the entry triggers (e.g. traps) on the tiles entities just moved onto.
+pub struct TrapTrigger;
+
+impl<'a> System<'a> for TrapTrigger {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        WriteExpect<'a, EffectQueue>,
+        WriteExpect<'a, GameLog>,
+        WriteStorage<'a, cmp::EntityMoved>,
+        WriteStorage<'a, cmp::Hidden>,
+        ReadStorage<'a, cmp::Pos>,
+        ReadStorage<'a, cmp::EntryTrigger>,
+        ReadStorage<'a, cmp::SingleActivation>,
+        ReadStorage<'a, cmp::Name>,
+        ReadStorage<'a, cmp::Player>,
+        EffectSources<'a>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut queue,
+            mut log,
+            mut moved,
+            mut hidden,
+            positions,
+            triggers,
+            single_activations,
+            names,
+            players,
+            sources,
+        ): Self::SystemData,
+    ) {
+        for (victim, _, pos) in (&entities, &moved, &positions).join() {
+            let traps: Vec<Entity> = (&entities, &triggers, &positions)
+                .join()
+                .filter(|(trap, _, trap_pos)| {
+                    *trap != victim && trap_pos.x == pos.x && trap_pos.y == pos.y
+                })
+                .map(|(trap, _, _)| trap)
+                .collect();
+
+            for trap in traps {
+                // A trap that went off is no secret anymore.
+                hidden.remove(trap);
+                if players.contains(victim) {
+                    log.log(format!("You trigger the {}!", name_of(&names, trap)));
+                }
+
+                for effect in effects::effects_of(&sources, trap) {
+                    queue.push(victim, effect);
+                }
+
+                if single_activations.contains(trap) {
+                    entities.delete(trap).expect("Unable to remove spent trap");
+                }
+            }
+        }
+
+        moved.clear();
+    }
+}
+
+/// Gives the player a chance to spot the hidden things in view (once per turn).
+pub struct Perception;
+
+impl<'a> System<'a> for Perception {
+    type SystemData = (
+        Entities<'a>,
+        WriteExpect<'a, GameLog>,
+        WriteStorage<'a, cmp::Hidden>,
+        ReadStorage<'a, cmp::Pos>,
+        ReadStorage<'a, cmp::Viewshed>,
+        ReadStorage<'a, cmp::Player>,
+        ReadStorage<'a, cmp::Name>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut log, mut hidden, positions, viewsheds, players, names): Self::SystemData,
+    ) {
+        let visible = match (&viewsheds, &players).join().next() {
+            Some((viewshed, _)) => &viewshed.visible_tiles,
+            None => return,
+        };
+
+        let mut rng = rand::thread_rng();
+        let spotted: Vec<Entity> = (&entities, &hidden, &positions)
+            .join()
+            .filter(|(_, _, pos)| visible.contains(&Point::new(pos.x, pos.y)))
+            .filter(|_| rng.gen_range(0, 100) < SPOT_CHANCE)
+            .map(|(e, _, _)| e)
+            .collect();
+
+        for thing in spotted {
+            hidden.remove(thing);
+            log.log(format!("You spot a {}.", name_of(&names, thing)));
+        }
+    }
+}
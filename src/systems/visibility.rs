@@ -4,27 +4,71 @@ use crate::map::base::*;
 use rltk::Point;
 use crate::utils::rect::Rect;
 
+/// How far blind entities can see
+const BLIND_RANGE: i32 = 1;
+
 /// Updates viewsheds using position and game map info.
 pub struct Visibility;
 
 impl<'a> System<'a> for Visibility {
     type SystemData = (
-        ReadExpect<'a, Map>,
+        WriteExpect<'a, Map>,
         WriteStorage<'a, cmp::Viewshed>,
         ReadStorage<'a, cmp::Pos>,
+        ReadStorage<'a, cmp::Blind>,
+        ReadStorage<'a, cmp::Player>,
+        ReadStorage<'a, cmp::Omniscient>,
     );
 
-    fn run(&mut self, (map, mut viewshed, pos): Self::SystemData) {
+    fn run(&mut self, (mut map, mut viewshed, pos, blind, player, omniscient): Self::SystemData) {
         // Update viewsheds.
-        for (viewshed, pos) in (&mut viewshed, &pos).join() {
-            // Retrieve normal reference to map from the ReadExpect thing.
-            let map = &*map;
+        for (viewshed, pos, blind, player, omniscient) in (
+            &mut viewshed,
+            &pos,
+            blind.maybe(),
+            player.maybe(),
+            omniscient.maybe(),
+        )
+            .join()
+        {
+            // Retrieve normal reference to map from the WriteExpect thing.
+            let map = &mut *map;
 
             viewshed.visible_tiles.clear();
-            viewshed.visible_tiles = rltk::field_of_view(Point::new(pos.x, pos.y), viewshed.range, map);
+            // Some see everything, the others whatever is in their field of view.
+            if omniscient.is_some() {
+                viewshed.visible_tiles = (0..map.tiles.len()).map(|idx| map.idx_xy(idx)).collect();
+            } else {
+                // Blindness shrinks the viewshed.
+                let range = if blind.is_some() {
+                    std::cmp::min(BLIND_RANGE, viewshed.range)
+                } else {
+                    viewshed.range
+                };
+                viewshed.visible_tiles =
+                    rltk::field_of_view(Point::new(pos.x, pos.y), range, &*map);
+
+                // The viewshed might go behond the map borders.
+                viewshed
+                    .visible_tiles
+                    .retain(|p| map.contains_point(p.x, p.y));
+
+                // Dark tiles can only be seen from up close.
+                let origin = Point::new(pos.x, pos.y);
+                let night_vision = viewshed.night_vision as f32;
+                viewshed.visible_tiles.retain(|&p| {
+                    map.is_lit(map.xy_idx(p.x, p.y))
+                        || rltk::DistanceAlg::Pythagoras.distance2d(origin, p) <= night_vision
+                });
+            }
 
-            // The viewshed might go behond the map borders.
-            viewshed.visible_tiles.retain(|p| map.contains_point(p.x, p.y));
+            // The player remembers what he/she has seen.
+            if player.is_some() {
+                for p in &viewshed.visible_tiles {
+                    let idx = map.xy_idx(p.x, p.y);
+                    map.revealed[idx] = true;
+                }
+            }
         }
     }
 }
@@ -0,0 +1,91 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Command line arguments of the game and the tools, read one option at a time
+///
+/// `-h` and `--help` are handled here: they print the usage and exit.
+pub struct Args<I> {
+    args: I,
+    usage: &'static str,
+    /// Option read last
+    option: String,
+}
+
+impl<I: Iterator<Item = String>> Args<I> {
+    /// Reads options from `args` (program name excluded), printing `usage` when asked for help
+    pub fn new(args: I, usage: &'static str) -> Self {
+        Args {
+            args,
+            usage,
+            option: String::new(),
+        }
+    }
+
+    /// Next option (`None` once all of them were read)
+    pub fn next_option(&mut self) -> Option<String> {
+        let option = self.args.next()?;
+        if option == "-h" || option == "--help" {
+            println!("{}", self.usage);
+            std::process::exit(0);
+        }
+        self.option = option.clone();
+        Some(option)
+    }
+
+    /// Value following the option just read
+    pub fn value(&mut self) -> Result<String, String> {
+        let option = &self.option;
+        self.args
+            .next()
+            .ok_or_else(|| format!("Missing value for {}", option))
+    }
+
+    /// Value following the option just read, parsed as a `T`
+    pub fn parse_value<T>(&mut self) -> Result<T, String>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let value = self.value()?;
+        value
+            .parse()
+            .map_err(|e| format!("Invalid value for {} ({}): {}", self.option, value, e))
+    }
+
+    /// Error for the option just read, which isn't a known one
+    pub fn unknown(&self) -> String {
+        format!("Unknown argument: {}\n\n{}", self.option, self.usage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Args<impl Iterator<Item = String> + '_> {
+        Args::new(line.split_whitespace().map(String::from), "usage")
+    }
+
+    #[test]
+    fn options_and_values_are_read_in_order() {
+        let mut args = args("--seed 42 --quiet --map a.ron");
+
+        assert_eq!(args.next_option().as_deref(), Some("--seed"));
+        assert_eq!(args.parse_value::<u64>(), Ok(42));
+        assert_eq!(args.next_option().as_deref(), Some("--quiet"));
+        assert_eq!(args.next_option().as_deref(), Some("--map"));
+        assert_eq!(args.value(), Ok("a.ron".to_string()));
+        assert_eq!(args.next_option(), None);
+    }
+
+    #[test]
+    fn bad_values_are_reported() {
+        let mut args = args("--seed x --runs");
+
+        args.next_option();
+        assert!(args.parse_value::<u64>().unwrap_err().contains("--seed"));
+        args.next_option();
+        assert_eq!(args.value(), Err("Missing value for --runs".to_string()));
+        assert!(args.unknown().starts_with("Unknown argument: --runs"));
+    }
+}
@@ -1,25 +1,134 @@
+use rand::distributions::{Distribution, Standard};
+use rand::Rng;
+use rltk::Point;
+use serde::{Deserialize, Serialize};
 use std::ops::{Add, Sub};
 
-#[derive(Clone, Copy, PartialEq, Debug)]
-/// Your run of the mill cardinal direction
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+/// Your run of the mill compass direction (cardinals and diagonals)
 pub enum Dir {
     North,
+    NorthEast,
+    East,
+    SouthEast,
     South,
+    SouthWest,
     West,
-    East,
+    NorthWest,
 }
 
 impl Dir {
-    /// Cycle through all directions
+    /// All directions, clockwise starting from north
+    pub const ALL: [Dir; 8] = [
+        Dir::North,
+        Dir::NorthEast,
+        Dir::East,
+        Dir::SouthEast,
+        Dir::South,
+        Dir::SouthWest,
+        Dir::West,
+        Dir::NorthWest,
+    ];
+
+    /// The four cardinal directions, clockwise starting from north
+    pub const CARDINALS: [Dir; 4] = [Dir::North, Dir::East, Dir::South, Dir::West];
+
+    /// Iterate over all directions (clockwise starting from north)
+    pub fn all() -> impl Iterator<Item = Dir> {
+        Self::ALL.iter().copied()
+    }
+
+    /// Iterate over the cardinal directions (clockwise starting from north)
+    pub fn cardinals() -> impl Iterator<Item = Dir> {
+        Self::CARDINALS.iter().copied()
+    }
+
+    /// Position of direction inside `Dir::ALL`
+    fn index(self) -> usize {
+        Self::ALL
+            .iter()
+            .position(|&d| d == self)
+            .expect("Direction missing from Dir::ALL")
+    }
+
+    /// Rotate clockwise by 45 degrees
+    pub fn cw(self) -> Self {
+        Self::ALL[(self.index() + 1) % 8]
+    }
+
+    /// Rotate counter-clockwise by 45 degrees
+    pub fn ccw(self) -> Self {
+        Self::ALL[(self.index() + 7) % 8]
+    }
+
+    /// Cycle through directions clockwise by 90 degrees (cardinals stay cardinals)
     pub fn cycle(self) -> Self {
+        self.cw().cw()
+    }
+
+    /// Direction pointing the other way
+    pub fn opposite(self) -> Self {
+        Self::ALL[(self.index() + 4) % 8]
+    }
+
+    /// Is this one of the four diagonals?
+    pub fn is_diagonal(self) -> bool {
+        let (dx, dy) = self.delta();
+        dx != 0 && dy != 0
+    }
+
+    /// Unit movement associated to direction (y grows southwards)
+    pub fn delta(self) -> (i32, i32) {
         use Dir::*;
         match self {
-            North => East,
-            East => South,
-            South => West,
-            West => North,
+            North => (0, -1),
+            NorthEast => (1, -1),
+            East => (1, 0),
+            SouthEast => (1, 1),
+            South => (0, 1),
+            SouthWest => (-1, 1),
+            West => (-1, 0),
+            NorthWest => (-1, -1),
         }
     }
+
+    /// Direction roughly pointing along `(dx, dy)` (only the signs matter)
+    ///
+    /// Returns `None` for the null vector.
+    pub fn from_delta((dx, dy): (i32, i32)) -> Option<Self> {
+        let delta = (dx.signum(), dy.signum());
+        Self::all().find(|d| d.delta() == delta)
+    }
+
+    /// Same as `from_delta`, but for rltk points
+    pub fn from_point(p: Point) -> Option<Self> {
+        Self::from_delta((p.x, p.y))
+    }
+
+    /// Random cardinal direction
+    pub fn random_cardinal<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Self::CARDINALS[rng.gen_range(0, 4)]
+    }
+}
+
+impl From<Dir> for (i32, i32) {
+    fn from(dir: Dir) -> Self {
+        dir.delta()
+    }
+}
+
+impl From<Dir> for Point {
+    fn from(dir: Dir) -> Self {
+        let (x, y) = dir.delta();
+        Point::new(x, y)
+    }
+}
+
+/// Allows sampling random directions (e.g. `rng.gen::<Dir>()`)
+impl Distribution<Dir> for Standard {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Dir {
+        Dir::ALL[rng.gen_range(0, 8)]
+    }
 }
 
 /// Trait for coordinate like things that can be moved in a particular direction
@@ -34,17 +143,64 @@ where
 {
     fn advance(self, dir: Dir, steps: R) -> Self {
         let (x, y) = self;
+        let (dx, dy) = dir.delta();
         (
-            match dir {
-                Dir::East => x + steps,
-                Dir::West => x - steps,
+            match dx {
+                1 => x + steps,
+                -1 => x - steps,
                 _ => x,
             },
-            match dir {
-                Dir::North => y - steps,
-                Dir::South => y + steps,
+            match dy {
+                1 => y + steps,
+                -1 => y - steps,
                 _ => y,
             },
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cw_and_ccw_are_inverse() {
+        for dir in Dir::all() {
+            assert_eq!(dir, dir.cw().ccw());
+            assert_eq!(dir, dir.ccw().cw());
+        }
+    }
+
+    #[test]
+    fn cycle_keeps_cardinals_cardinal() {
+        for dir in Dir::cardinals() {
+            assert!(!dir.cycle().is_diagonal());
+        }
+        assert_eq!(Dir::North.cycle(), Dir::East);
+        assert_eq!(Dir::West.cycle(), Dir::North);
+    }
+
+    #[test]
+    fn opposite_negates_delta() {
+        for dir in Dir::all() {
+            let (dx, dy) = dir.delta();
+            assert_eq!(dir.opposite().delta(), (-dx, -dy));
+        }
+    }
+
+    #[test]
+    fn from_delta_inverts_delta() {
+        for dir in Dir::all() {
+            assert_eq!(Dir::from_delta(dir.delta()), Some(dir));
+            assert_eq!(Dir::from_point(Point::from(dir)), Some(dir));
+        }
+        assert_eq!(Dir::from_delta((0, 0)), None);
+        assert_eq!(Dir::from_delta((5, -3)), Some(Dir::NorthEast));
+    }
+
+    #[test]
+    fn advance_diagonally() {
+        assert_eq!((3, 3).advance(Dir::SouthWest, 2), (1, 5));
+        assert_eq!((3, 3).advance(Dir::North, 1), (3, 2));
+    }
+}
@@ -1,2 +1,4 @@
+pub mod args;
 pub mod rect;
 pub mod dir;
+pub mod random_table;
@@ -0,0 +1,76 @@
+use rand::Rng;
+
+/// A table of values, each one with its own weight, that can be randomly rolled
+pub struct RandomTable<T> {
+    entries: Vec<(T, u32)>,
+    total_weight: u32,
+}
+
+impl<T> RandomTable<T> {
+    /// Empty table
+    pub fn new() -> Self {
+        RandomTable {
+            entries: vec![],
+            total_weight: 0,
+        }
+    }
+
+    /// Add `value` with given `weight` (values with no weight are never rolled)
+    pub fn add(mut self, value: T, weight: u32) -> Self {
+        if weight > 0 {
+            self.total_weight += weight;
+            self.entries.push((value, weight));
+        }
+        self
+    }
+
+    /// Is there anything to roll?
+    pub fn is_empty(&self) -> bool {
+        self.total_weight == 0
+    }
+
+    /// Pick a value with probability proportional to its weight
+    pub fn roll<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<&T> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let mut roll = rng.gen_range(0, self.total_weight);
+        for (value, weight) in &self.entries {
+            if roll < *weight {
+                return Some(value);
+            }
+            roll -= weight;
+        }
+
+        unreachable!("Roll exceeded total weight of random table")
+    }
+}
+
+impl<T> Default for RandomTable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_table_rolls_nothing() {
+        let table: RandomTable<i32> = RandomTable::new().add(1, 0);
+
+        assert!(table.is_empty());
+        assert_eq!(table.roll(&mut rand::thread_rng()), None);
+    }
+
+    #[test]
+    fn weightless_values_are_never_rolled() {
+        let table = RandomTable::new().add("never", 0).add("always", 3);
+
+        for _ in 0..100 {
+            assert_eq!(table.roll(&mut rand::thread_rng()), Some(&"always"));
+        }
+    }
+}
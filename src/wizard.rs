@@ -0,0 +1,376 @@
+use crate::components as cmp;
+use crate::game_state::{player_entity, player_pos, State};
+use crate::input::{self, Action};
+use crate::map::gen::{self, rnc, Generated};
+use crate::map::history::History;
+use crate::map::pathfinding::Pathfinder;
+use crate::map::Map;
+use crate::raws::{Raws, TemplateKind};
+use crate::spawner;
+use crate::utils::dir::Dir;
+use crate::utils::rect::Rect;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rltk::{Point, Rltk, VirtualKeyCode};
+use specs::prelude::*;
+use std::collections::HashSet;
+
+/// Most lines kept in the console history
+const MAX_LINES: usize = 100;
+
+/// What the `help` command prints
+const HELP: [&str; 8] = [
+    "reveal: reveal the whole map",
+    "teleport X Y: move the player to (X, Y)",
+    "spawn TEMPLATE: spawn something next to the player",
+    "god: toggle invulnerability",
+    "regen GENERATOR SEED: generate a new map",
+    "fov on|off: toggle the player's field of view",
+    "give ITEM: put an item in the player's backpack",
+    "help: print this list",
+];
+
+/// A wizard console command
+#[derive(PartialEq, Clone, Debug)]
+pub enum Command {
+    Help,
+    Reveal,
+    Teleport { x: i32, y: i32 },
+    Spawn(String),
+    God,
+    Regen { generator: String, seed: u64 },
+    Fov(bool),
+    Give(String),
+}
+
+impl Command {
+    /// Parse command from a line typed in the console
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (name, args) = match words.split_first() {
+            Some((name, args)) => (*name, args),
+            None => return Err("Type a command (`help` lists them).".to_string()),
+        };
+        // Template names can have spaces.
+        let rest = args.join(" ");
+        let number = |word: &str| word.parse().map_err(|_| format!("Not a number: {}", word));
+
+        Ok(match (name, args) {
+            ("help", []) => Command::Help,
+            ("reveal", []) => Command::Reveal,
+            ("teleport", &[x, y]) => Command::Teleport {
+                x: number(x)?,
+                y: number(y)?,
+            },
+            ("spawn", _) if !args.is_empty() => Command::Spawn(rest),
+            ("god", []) => Command::God,
+            ("regen", &[generator, seed]) => Command::Regen {
+                generator: generator.to_string(),
+                seed: seed.parse().map_err(|_| format!("Not a seed: {}", seed))?,
+            },
+            ("fov", ["on"]) => Command::Fov(true),
+            ("fov", ["off"]) => Command::Fov(false),
+            ("give", _) if !args.is_empty() => Command::Give(rest),
+            _ => return Err(format!("Can't make sense of `{}` (try `help`).", line)),
+        })
+    }
+}
+
+/// The wizard console: what's being typed and what happened so far
+#[derive(Default)]
+pub struct WizardConsole {
+    pub input: String,
+    /// Commands run and their outcome, oldest first
+    pub lines: Vec<String>,
+}
+
+impl WizardConsole {
+    /// Add a line to the history (forgetting the oldest ones if there are too many)
+    fn print<S: Into<String>>(&mut self, line: S) {
+        self.lines.push(line.into());
+        if self.lines.len() > MAX_LINES {
+            self.lines.remove(0);
+        }
+    }
+}
+
+/// Character typed with `key` (letters are lowercase, there's no shift)
+fn typed_char(key: VirtualKeyCode) -> Option<char> {
+    use VirtualKeyCode::*;
+    let letters = [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    ];
+    let digits = [Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+    let numpad = [
+        Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+    ];
+
+    if let Some(i) = letters.iter().position(|&k| k == key) {
+        return Some((b'a' + i as u8) as char);
+    }
+    if let Some(i) = digits
+        .iter()
+        .position(|&k| k == key)
+        .or_else(|| numpad.iter().position(|&k| k == key))
+    {
+        return Some((b'0' + i as u8) as char);
+    }
+    match key {
+        Space => Some(' '),
+        Minus | Subtract => Some('-'),
+        _ => None,
+    }
+}
+
+/// Name of the template of kind `kind` called `name` (ignoring case), if any
+fn find_template(ecs: &World, name: &str, kinds: &[TemplateKind]) -> Option<String> {
+    let raws = ecs.fetch::<Raws>();
+    let template = kinds
+        .iter()
+        .flat_map(|&kind| raws.names_of_kind(kind))
+        .find(|template| template.eq_ignore_ascii_case(name))
+        .map(String::from);
+    template
+}
+
+/// A free passable tile next to `p` (if any)
+fn free_tile_near(ecs: &World, p: Point) -> Option<Point> {
+    let map = ecs.fetch::<Map>();
+    let positions = ecs.read_storage::<cmp::Pos>();
+    Dir::all()
+        .map(|dir| {
+            let (dx, dy) = dir.delta();
+            Point::new(p.x + dx, p.y + dy)
+        })
+        .filter(|t| map.contains_point(t.x, t.y) && map.at(t.x, t.y).is_passable())
+        .find(|t| !positions.join().any(|pos| pos.x == t.x && pos.y == t.y))
+}
+
+/// Adds (or removes, if `on` is false) marker component `C` to the player
+fn set_player_marker<C: Component + Default>(ecs: &mut World, on: bool) {
+    let player = player_entity(ecs);
+    let mut storage = ecs.write_storage::<C>();
+    if on {
+        storage
+            .insert(player, C::default())
+            .expect("Unable to mark player");
+    } else {
+        storage.remove(player);
+    }
+}
+
+/// Replaces the current map with a new one made by `generator` from `seed`, populating it anew
+/// (the player keeps what he/she carries)
+fn regenerate(ecs: &mut World, generator: &str, seed: u64) -> Result<String, String> {
+    // Only record the generation if the old map's was.
+    let mut history = if ecs.fetch::<History>().is_recording() {
+        History::recording()
+    } else {
+        History::default()
+    };
+    let Generated { mut map, complete } =
+        gen::run(generator, rnc::Config::game(), seed, &mut history)?;
+    map.movement = ecs.fetch::<Map>().movement.clone();
+    let mut rng = StdRng::seed_from_u64(seed);
+    crate::map::theming::apply(&mut map, &mut rng);
+    history.record(&map, None);
+
+    // Whatever lies on the old map goes away with it (along with what it carries).
+    let player = player_entity(ecs);
+    let doomed: Vec<Entity> = {
+        let entities = ecs.entities();
+        let positions = ecs.read_storage::<cmp::Pos>();
+        let backpacks = ecs.read_storage::<cmp::InBackpack>();
+        let equipped = ecs.read_storage::<cmp::Equipped>();
+        let on_map: HashSet<Entity> = (&entities, &positions)
+            .join()
+            .map(|(e, _)| e)
+            .filter(|&e| e != player)
+            .collect();
+        let carried = (&entities, &backpacks)
+            .join()
+            .filter(|(_, backpack)| on_map.contains(&backpack.owner))
+            .map(|(e, _)| e);
+        let worn = (&entities, &equipped)
+            .join()
+            .filter(|(_, equipped)| on_map.contains(&equipped.owner))
+            .map(|(e, _)| e);
+        let carried: Vec<Entity> = carried.chain(worn).collect();
+        on_map.into_iter().chain(carried).collect()
+    };
+    ecs.delete_entities(&doomed)
+        .expect("Unable to clear old map");
+    ecs.maintain();
+
+    let start = spawner::pick_start(&map, &mut rng);
+    ecs.write_storage::<cmp::Pos>()
+        .insert(player, cmp::Pos::from(start))
+        .expect("Unable to move player");
+    spawner::populate(ecs, &mut map, 1, start, &mut rng);
+
+    ecs.insert(map);
+    ecs.insert(history);
+    ecs.insert(Pathfinder::default());
+
+    let outcome = if complete {
+        ""
+    } else {
+        " (stopped prematurely)"
+    };
+    Ok(format!(
+        "Generated a new map with {} from seed {}{}.",
+        generator, seed, outcome
+    ))
+}
+
+/// Runs `command` against the world, returning what came out of it
+pub fn run(ecs: &mut World, command: Command) -> Result<String, String> {
+    match command {
+        Command::Help => Ok(HELP.join("\n")),
+        Command::Reveal => {
+            let mut map = ecs.fetch_mut::<Map>();
+            map.revealed.iter_mut().for_each(|r| *r = true);
+            Ok("The whole map is revealed.".to_string())
+        }
+        Command::Teleport { x, y } => {
+            let passable = {
+                let map = ecs.fetch::<Map>();
+                map.contains_point(x, y) && map.at(x, y).is_passable()
+            };
+            if !passable {
+                return Err(format!("Can't teleport to ({}, {}).", x, y));
+            }
+            let player = player_entity(ecs);
+            ecs.write_storage::<cmp::Pos>()
+                .insert(player, cmp::Pos { x, y })
+                .expect("Unable to move player");
+            ecs.write_storage::<cmp::EntityMoved>()
+                .insert(player, cmp::EntityMoved)
+                .expect("Unable to insert entity moved");
+            Ok(format!("Teleported to ({}, {}).", x, y))
+        }
+        Command::Spawn(name) => {
+            let kinds = [
+                TemplateKind::Mob,
+                TemplateKind::Item,
+                TemplateKind::Npc,
+                TemplateKind::Trap,
+            ];
+            let template = find_template(ecs, &name, &kinds)
+                .ok_or_else(|| format!("There is no template called {}.", name))?;
+            let pos = free_tile_near(ecs, player_pos(ecs))
+                .ok_or_else(|| "There is no room next to the player.".to_string())?;
+            spawner::spawn_named(ecs, &template, pos);
+            Ok(format!("Spawned {} at ({}, {}).", template, pos.x, pos.y))
+        }
+        Command::God => {
+            let player = player_entity(ecs);
+            let god = !ecs.read_storage::<cmp::Invulnerable>().contains(player);
+            set_player_marker::<cmp::Invulnerable>(ecs, god);
+            Ok(format!("God mode {}.", if god { "on" } else { "off" }))
+        }
+        Command::Regen { generator, seed } => regenerate(ecs, &generator, seed),
+        Command::Fov(on) => {
+            set_player_marker::<cmp::Omniscient>(ecs, !on);
+            Ok(format!("Field of view {}.", if on { "on" } else { "off" }))
+        }
+        Command::Give(name) => {
+            let template = find_template(ecs, &name, &[TemplateKind::Item])
+                .ok_or_else(|| format!("There is no item called {}.", name))?;
+            let player = player_entity(ecs);
+            let item = spawner::spawn_named(ecs, &template, player_pos(ecs))
+                .expect("Unable to spawn item");
+            ecs.write_storage::<cmp::Pos>().remove(item);
+            ecs.write_storage::<cmp::InBackpack>()
+                .insert(item, cmp::InBackpack { owner: player })
+                .expect("Unable to give item");
+            Ok(format!("You get a {}.", template))
+        }
+    }
+}
+
+/// Handles input while the console is open. Returns whether to keep it open.
+pub fn input(gs: &mut State, ctx: &Rltk) -> bool {
+    let key = match ctx.key {
+        None => return true,
+        Some(key) => key,
+    };
+
+    let ecs = &mut gs.ecs;
+    match key {
+        VirtualKeyCode::Return => {
+            let line = std::mem::take(&mut ecs.fetch_mut::<WizardConsole>().input);
+            let command = Command::parse(&line);
+            // Wherever the player was travelling to is gone along with the old map.
+            if let Ok(Command::Regen { .. }) = command {
+                gs.autotravel = None;
+            }
+            let outcome = command.and_then(|command| run(ecs, command));
+            let mut console = ecs.fetch_mut::<WizardConsole>();
+            console.print(format!("> {}", line));
+            for line in outcome.unwrap_or_else(|e| e).lines() {
+                console.print(line);
+            }
+        }
+        VirtualKeyCode::Back => {
+            ecs.fetch_mut::<WizardConsole>().input.pop();
+        }
+        _ => match typed_char(key) {
+            Some(c) => ecs.fetch_mut::<WizardConsole>().input.push(c),
+            None => {
+                if let Some(Action::WizardConsole) | Some(Action::Cancel) =
+                    input::pressed_action(ecs, ctx)
+                {
+                    return false;
+                }
+            }
+        },
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_are_parsed() {
+        assert_eq!(
+            Command::parse(" teleport 3  4 "),
+            Ok(Command::Teleport { x: 3, y: 4 })
+        );
+        assert_eq!(
+            Command::parse("spawn health potion"),
+            Ok(Command::Spawn("health potion".to_string()))
+        );
+        assert_eq!(
+            Command::parse("regen ugly 42"),
+            Ok(Command::Regen {
+                generator: "ugly".to_string(),
+                seed: 42
+            })
+        );
+        assert_eq!(Command::parse("fov off"), Ok(Command::Fov(false)));
+        assert_eq!(Command::parse("god"), Ok(Command::God));
+    }
+
+    #[test]
+    fn bad_commands_are_rejected() {
+        assert!(Command::parse("").is_err());
+        assert!(Command::parse("teleport 3").is_err());
+        assert!(Command::parse("teleport x 4").is_err());
+        assert!(Command::parse("regen rnc -1").is_err());
+        assert!(Command::parse("fov maybe").is_err());
+        assert!(Command::parse("spawn").is_err());
+        assert!(Command::parse("fly").is_err());
+    }
+
+    #[test]
+    fn keys_type_lowercase_text() {
+        assert_eq!(typed_char(VirtualKeyCode::Q), Some('q'));
+        assert_eq!(typed_char(VirtualKeyCode::Key7), Some('7'));
+        assert_eq!(typed_char(VirtualKeyCode::Numpad0), Some('0'));
+        assert_eq!(typed_char(VirtualKeyCode::Space), Some(' '));
+        assert_eq!(typed_char(VirtualKeyCode::Escape), None);
+    }
+}